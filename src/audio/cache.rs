@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use symphonia_core::io::MediaSource;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Extension of finished cache files.
+const EXTENSION: &str = "audio";
+/// Extension of files which are still being written, left over ones are removed on startup.
+const PARTIAL_EXTENSION: &str = "part";
+
+struct Entry {
+    size: u64,
+    last_used: SystemTime,
+}
+
+pub struct CacheStats {
+    pub entries: usize,
+    pub size: u64,
+    pub limit: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Streams which were played before, kept on disk so they are not downloaded again.
+///
+/// Files are named after the video id and format, and the least recently used ones are removed
+/// once the cache grows past its size limit.
+pub struct AudioCache {
+    dir: PathBuf,
+    /// Size limit in bytes.
+    limit: u64,
+    entries: Mutex<HashMap<String, Entry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl AudioCache {
+    /// Opens the cache in a directory, creating it if needed and indexing the files already there.
+    pub fn open(dir: impl Into<PathBuf>, limit: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut entries = HashMap::new();
+        for file in fs::read_dir(&dir)? {
+            let path = file?.path();
+            let extension = path.extension().and_then(|ext| ext.to_str());
+            if extension == Some(PARTIAL_EXTENSION) {
+                // interrupted while streaming, so it is incomplete
+                fs::remove_file(&path)?;
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if extension == Some(EXTENSION) {
+                let metadata = path.metadata()?;
+                entries.insert(
+                    key.to_owned(),
+                    Entry {
+                        size: metadata.len(),
+                        last_used: metadata.modified()?,
+                    },
+                );
+            }
+        }
+
+        let cache = AudioCache {
+            dir,
+            limit,
+            entries: Mutex::new(entries),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    fn key(video_id: &str, itag: &str) -> String {
+        format!("{video_id}-{itag}")
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{EXTENSION}"))
+    }
+
+    /// Opens the cached stream of a video, if it was cached before.
+    pub fn get(&self, video_id: &str, itag: &str) -> Option<File> {
+        let key = Self::key(video_id, itag);
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&key) {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        let file = match File::open(self.path(&key)) {
+            Ok(file) => file,
+            Err(why) => {
+                warn!("could not open cached stream {key}: {why:?}");
+                entries.remove(&key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        };
+
+        let now = SystemTime::now();
+        if let Some(entry) = entries.get_mut(&key) {
+            entry.last_used = now;
+        }
+        // the modification time keeps track of use across restarts, losing it only affects eviction
+        drop(file.set_modified(now));
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(file)
+    }
+
+    /// Starts caching a stream, which is only added once it was streamed to the end.
+    pub fn writer(self: &Arc<Self>, video_id: &str, itag: &str) -> Option<CacheWriter> {
+        let key = Self::key(video_id, itag);
+        // concurrent plays of the same track each write their own file
+        let partial = self
+            .dir
+            .join(format!("{key}-{}.{PARTIAL_EXTENSION}", Uuid::new_v4()));
+
+        match File::create(&partial) {
+            Ok(file) => Some(CacheWriter {
+                cache: self.clone(),
+                key,
+                partial,
+                file: BufWriter::new(file),
+                written: 0,
+                done: false,
+            }),
+            Err(why) => {
+                warn!("could not create cache file {}: {why:?}", partial.display());
+                None
+            }
+        }
+    }
+
+    fn insert(&self, key: String, partial: &Path, size: u64) -> io::Result<()> {
+        fs::rename(partial, self.path(&key))?;
+        self.entries.lock().unwrap().insert(
+            key,
+            Entry {
+                size,
+                last_used: SystemTime::now(),
+            },
+        );
+        self.evict();
+        Ok(())
+    }
+
+    /// Removes the least recently used files until the cache fits its limit.
+    fn evict(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut size: u64 = entries.values().map(|entry| entry.size).sum();
+        if size <= self.limit {
+            return;
+        }
+
+        let mut keys: Vec<_> = entries
+            .iter()
+            .map(|(key, entry)| (entry.last_used, key.clone()))
+            .collect();
+        keys.sort();
+
+        for (_, key) in keys {
+            if size <= self.limit {
+                break;
+            }
+            // open files stay readable after removal, so tracks playing from it are unaffected
+            if let Err(why) = fs::remove_file(self.path(&key)) {
+                warn!("could not evict cached stream {key}: {why:?}");
+            }
+            if let Some(entry) = entries.remove(&key) {
+                size -= entry.size;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            entries: entries.len(),
+            size: entries.values().map(|entry| entry.size).sum(),
+            limit: self.limit,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes every cached stream and returns how many bytes were freed.
+    pub fn purge(&self) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let mut freed = 0;
+        for (key, entry) in entries.drain() {
+            // the rest is removed anyway, a file left behind is indexed again on the next start
+            match fs::remove_file(self.path(&key)) {
+                Ok(()) => freed += entry.size,
+                Err(why) => warn!("could not purge cached stream {key}: {why:?}"),
+            }
+        }
+        freed
+    }
+}
+
+/// Writes a stream into the cache as it is read for the first time.
+pub struct CacheWriter {
+    cache: Arc<AudioCache>,
+    key: String,
+    partial: PathBuf,
+    file: BufWriter<File>,
+    written: u64,
+    done: bool,
+}
+
+impl CacheWriter {
+    fn finish(&mut self) -> io::Result<()> {
+        self.done = true;
+        self.file.flush()?;
+        self.cache
+            .insert(self.key.clone(), &self.partial, self.written)
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        // finished files were moved already, anything left was not read to the end and is useless
+        if !self.done || self.partial.exists() {
+            drop(fs::remove_file(&self.partial));
+        }
+    }
+}
+
+/// Passes a stream through while writing it to the cache.
+///
+/// Only bytes which continue the cached part are written, so the file never has holes. Seeking
+/// back, as probing does, is harmless, but after skipping ahead the rest is only cached if the
+/// stream is read from where the cached part ends again. Otherwise the partial file is dropped.
+pub struct CachingSource {
+    inner: Box<dyn MediaSource>,
+    writer: Option<CacheWriter>,
+    /// Where the next read starts in the stream.
+    position: u64,
+}
+
+impl CachingSource {
+    pub fn new(inner: Box<dyn MediaSource>, writer: CacheWriter) -> Self {
+        CachingSource {
+            inner,
+            writer: Some(writer),
+            position: 0,
+        }
+    }
+}
+
+impl Read for CachingSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.position;
+        let len = self.inner.read(buf)?;
+        self.position += len as u64;
+        let Some(writer) = &mut self.writer else {
+            return Ok(len);
+        };
+
+        let result = if start > writer.written {
+            // past a hole, which is only filled by going back to where the cached part ends
+            Ok(())
+        } else if len == 0 {
+            match self.inner.byte_len() {
+                Some(size) if size != writer.written => Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("stream ended after {} of {size} bytes", writer.written),
+                )),
+                _ => writer.finish(),
+            }
+        } else if self.position <= writer.written {
+            // read again after seeking back
+            Ok(())
+        } else {
+            let new = &buf[(writer.written - start) as usize..len];
+            writer.written += new.len() as u64;
+            match writer.file.write_all(new) {
+                Ok(()) if Some(writer.written) == self.inner.byte_len() => writer.finish(),
+                result => result,
+            }
+        };
+        if let Err(why) = result {
+            warn!("could not cache stream {}: {why:?}", writer.key);
+            self.writer = None;
+        } else if writer.done {
+            self.writer = None;
+        }
+        Ok(len)
+    }
+}
+
+impl Seek for CachingSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for CachingSource {
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.inner.byte_len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, time::Duration};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("audio-cache-{}-{name}", std::process::id()));
+        drop(fs::remove_dir_all(&dir));
+        dir
+    }
+
+    fn stream(cache: &Arc<AudioCache>, data: &[u8]) -> CachingSource {
+        let writer = cache.writer("video", "251").unwrap();
+        CachingSource::new(Box::new(Cursor::new(data.to_vec())), writer)
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = temp_dir("evict");
+        fs::create_dir_all(&dir).unwrap();
+        let old = File::create(dir.join("old-251.audio")).unwrap();
+        old.set_len(6).unwrap();
+        old.set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        File::create(dir.join("new-251.audio"))
+            .unwrap()
+            .set_len(6)
+            .unwrap();
+        File::create(dir.join("cut-251-1.part")).unwrap();
+
+        let cache = AudioCache::open(&dir, 10).unwrap();
+        assert!(!dir.join("old-251.audio").exists());
+        assert!(dir.join("new-251.audio").exists());
+        assert!(!dir.join("cut-251-1.part").exists());
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.size), (1, 6));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn caches_stream_read_after_seeking_back() {
+        let dir = temp_dir("seek-back");
+        let cache = Arc::new(AudioCache::open(&dir, 1000).unwrap());
+        let data: Vec<u8> = (0..100).collect();
+
+        let mut source = stream(&cache, &data);
+        let mut buf = [0; 10];
+        source.read_exact(&mut buf).unwrap();
+        source.seek(SeekFrom::Start(50)).unwrap();
+        source.read_exact(&mut buf).unwrap();
+        source.seek(SeekFrom::Start(5)).unwrap();
+        source.read_to_end(&mut Vec::new()).unwrap();
+        drop(source);
+
+        let mut cached = Vec::new();
+        cache
+            .get("video", "251")
+            .unwrap()
+            .read_to_end(&mut cached)
+            .unwrap();
+        assert_eq!(cached, data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drops_stream_with_a_hole() {
+        let dir = temp_dir("hole");
+        let cache = Arc::new(AudioCache::open(&dir, 1000).unwrap());
+        let data: Vec<u8> = (0..100).collect();
+
+        let mut source = stream(&cache, &data);
+        source.read_exact(&mut [0; 10]).unwrap();
+        source.seek(SeekFrom::Start(50)).unwrap();
+        source.read_to_end(&mut Vec::new()).unwrap();
+        drop(source);
+
+        assert!(cache.get("video", "251").is_none());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
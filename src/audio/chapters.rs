@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use super::timestamp::parse_clock;
+
+/// YouTube only shows chapters when a video has at least this many.
+const MIN_CHAPTERS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub start: Duration,
+    pub title: String,
+}
+
+/// Reads the chapters YouTube makes out of the timestamps in a video's description.
+///
+/// Like on YouTube, the timestamps only count as chapters when the first one is at 0:00, they go
+/// forward in time and there are at least three of them.
+pub fn parse_chapters(description: &str) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for line in description.lines() {
+        let Some((start, title)) = parse_line(line) else {
+            continue;
+        };
+        let in_order = match chapters.last() {
+            Some(last) => start > last.start,
+            // timestamps mentioned in the text before the list are not chapters
+            None => start == Duration::ZERO,
+        };
+        if in_order {
+            chapters.push(Chapter { start, title });
+        }
+    }
+
+    if chapters.len() < MIN_CHAPTERS {
+        chapters.clear();
+    }
+    chapters
+}
+
+/// Returns the chapter playing at `position` along with its index.
+pub fn current_chapter(chapters: &[Chapter], position: Duration) -> Option<(usize, &Chapter)> {
+    chapters
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, chapter)| chapter.start <= position)
+}
+
+/// Splits a line such as `12:34 - Title` or `[1:02:03] Title` into its timestamp and title.
+fn parse_line(line: &str) -> Option<(Duration, String)> {
+    let (word, start) = line.split_whitespace().find_map(|word| {
+        // plain numbers are too common in titles, so only timestamps with colons count
+        let timestamp = word.trim_matches(|c| matches!(c, '(' | ')' | '[' | ']'));
+        parse_clock(timestamp).map(|start| (word, start))
+    })?;
+
+    let title = line.replacen(word, "", 1);
+    let title = title.trim_matches(|c: char| {
+        c.is_whitespace() || matches!(c, '-' | '–' | '—' | '|' | ':' | '•')
+    });
+    (!title.is_empty()).then(|| (start, title.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(description: &str) -> Vec<(u64, String)> {
+        parse_chapters(description)
+            .into_iter()
+            .map(|chapter| (chapter.start.as_secs(), chapter.title))
+            .collect()
+    }
+
+    #[test]
+    fn reads_chapter_lines() {
+        let description = "Tracklist:\n\
+            0:00 - Intro\n\
+            [1:02:03] Second part\n\
+            (1:10:00) | Third part\n\
+            Thanks for listening, 3 more songs soon";
+        assert_eq!(
+            parsed(description),
+            [
+                (0, "Intro".to_owned()),
+                (3723, "Second part".to_owned()),
+                (4200, "Third part".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn chapters_start_at_zero() {
+        // a timestamp mentioned before the list does not start it
+        let description = "Best part at 2:00\n0:00 Intro\n1:00 Verse\n3:00 Outro";
+        assert_eq!(
+            parsed(description),
+            [
+                (0, "Intro".to_owned()),
+                (60, "Verse".to_owned()),
+                (180, "Outro".to_owned()),
+            ]
+        );
+        assert!(parsed("0:30 Intro\n1:00 Verse\n3:00 Outro").is_empty());
+    }
+
+    #[test]
+    fn chapters_go_forward() {
+        let description = "0:00 Intro\n2:00 Verse\n1:00 Back in time\n2:00 Again\n3:00 Outro";
+        assert_eq!(
+            parsed(description),
+            [
+                (0, "Intro".to_owned()),
+                (120, "Verse".to_owned()),
+                (180, "Outro".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_three_chapters() {
+        assert!(parsed("0:00 Intro\n1:00 Outro").is_empty());
+        assert!(parsed("no timestamps at all").is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::timestamp::parse_timestamp;
+
+/// Part of a track to play instead of all of it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Clip {
+    pub start: Duration,
+    /// Where the track ends early, it plays to its end when unset.
+    pub end: Option<Duration>,
+}
+
+impl Clip {
+    /// Reads the range a link asks for with YouTube's `t`, `start` and `end` parameters.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = reqwest::Url::parse(url).ok()?;
+        let mut start = None;
+        let mut end = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "t" | "start" => start = parse_timestamp(&value),
+                "end" => end = parse_timestamp(&value),
+                _ => {}
+            }
+        }
+
+        let clip = Clip {
+            start: start.unwrap_or_default(),
+            end,
+        };
+        (clip.start > Duration::ZERO || clip.end.is_some()).then_some(clip)
+    }
+
+    /// Whether the clip starts within a track of the given duration and before it ends.
+    pub fn fits(&self, duration: Duration) -> bool {
+        self.start < duration && self.end.is_none_or(|end| end > self.start)
+    }
+
+    /// How long the clip plays of a track of the given duration.
+    pub fn length(&self, duration: Duration) -> Duration {
+        self.end
+            .unwrap_or(duration)
+            .min(duration)
+            .saturating_sub(self.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(start: u64, end: Option<u64>) -> Option<Clip> {
+        Some(Clip {
+            start: Duration::from_secs(start),
+            end: end.map(Duration::from_secs),
+        })
+    }
+
+    #[test]
+    fn reads_range_from_links() {
+        let from_url = Clip::from_url;
+        assert_eq!(
+            from_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=90"),
+            clip(90, None)
+        );
+        assert_eq!(
+            from_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1m30s"),
+            clip(90, None)
+        );
+        assert_eq!(
+            from_url("https://youtu.be/dQw4w9WgXcQ?t=90"),
+            clip(90, None)
+        );
+        assert_eq!(
+            from_url("https://www.youtube.com/embed/dQw4w9WgXcQ?start=10&end=20"),
+            clip(10, Some(20))
+        );
+        assert_eq!(from_url("https://youtu.be/dQw4w9WgXcQ"), None);
+        assert_eq!(from_url("https://youtu.be/dQw4w9WgXcQ?t=0"), None);
+        assert_eq!(from_url("https://youtu.be/dQw4w9WgXcQ?t=soon"), None);
+    }
+
+    #[test]
+    fn fits_within_track() {
+        let duration = Duration::from_secs(100);
+        assert!(clip(10, Some(20)).unwrap().fits(duration));
+        assert!(clip(10, Some(200)).unwrap().fits(duration));
+        assert!(clip(99, None).unwrap().fits(duration));
+        assert!(!clip(100, None).unwrap().fits(duration));
+        assert!(!clip(20, Some(10)).unwrap().fits(duration));
+        assert!(!clip(20, Some(20)).unwrap().fits(duration));
+    }
+}
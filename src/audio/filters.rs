@@ -0,0 +1,1071 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::{FRAC_1_SQRT_2, PI, SQRT_2},
+    io::{self, Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use songbird::input::{
+    codecs::{get_codec_registry, get_probe},
+    AudioStream, AudioStreamError, AuxMetadata, Compose, RawAdapter,
+};
+use symphonia::core::{
+    audio::{Channels, SampleBuffer},
+    codecs::{Decoder, DecoderOptions},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSourceStream, MediaSourceStreamOptions},
+    meta::MetadataOptions,
+    units::{Time, TimeBase},
+};
+use symphonia_core::io::MediaSource;
+
+/// Filters always output stereo.
+const CHANNELS: usize = 2;
+/// Bytes of one output frame, a f32 sample per channel.
+const FRAME_BYTES: u64 = (CHANNELS * 4) as u64;
+/// Corner frequencies of the bass and treble shelves.
+const BASS_HZ: f32 = 100.0;
+const TREBLE_HZ: f32 = 8000.0;
+/// Integrated loudness tracks are normalized to, in LUFS.
+const TARGET_LOUDNESS: f64 = -14.0;
+/// Bounds of the normalization gain in dB, quiet tracks are only raised a little to not blow up
+/// their noise.
+const MIN_GAIN: f32 = -15.0;
+const MAX_GAIN: f32 = 9.0;
+/// How fast the normalization gain may change, in dB per second.
+const GAIN_SLEW: f32 = 6.0;
+/// Loudness is measured in blocks of this many seconds, as in EBU R128.
+const BLOCK_SECONDS: f32 = 0.4;
+/// Blocks measured before the gain follows the measurement, so a quiet intro is not blown up.
+const SETTLE_BLOCKS: usize = 8;
+/// Blocks the measurement is based on, the last ten minutes so live streams follow their loudness.
+const MEASURED_BLOCKS: usize = 1500;
+/// Blocks quieter than this in LUFS are silence and left out of the measurement.
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Filter presets offered by `/filter`.
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum FilterPreset {
+    Off,
+    #[name = "Bass Boost"]
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    #[name = "8D"]
+    EightD,
+    Karaoke,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterSettings {
+    /// Gain of the bass shelf in dB.
+    pub bass: f32,
+    /// Gain of the treble shelf in dB.
+    pub treble: f32,
+    /// Playback speed, which changes the pitch along with it like a record would.
+    pub speed: f32,
+    /// How many times per second the sound circles between the left and right channel.
+    pub rotation: f32,
+    /// Removes whatever is panned to the center, which usually is the vocals.
+    pub karaoke: bool,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        FilterSettings {
+            bass: 0.0,
+            treble: 0.0,
+            speed: 1.0,
+            rotation: 0.0,
+            karaoke: false,
+        }
+    }
+}
+
+impl From<FilterPreset> for FilterSettings {
+    fn from(preset: FilterPreset) -> Self {
+        let off = FilterSettings::default();
+        match preset {
+            FilterPreset::Off => off,
+            FilterPreset::BassBoost => FilterSettings { bass: 8.0, ..off },
+            FilterPreset::Nightcore => FilterSettings {
+                speed: 1.25,
+                treble: 2.0,
+                ..off
+            },
+            FilterPreset::Vaporwave => FilterSettings {
+                speed: 0.8,
+                bass: 3.0,
+                ..off
+            },
+            FilterPreset::EightD => FilterSettings {
+                rotation: 0.125,
+                ..off
+            },
+            FilterPreset::Karaoke => FilterSettings {
+                karaoke: true,
+                ..off
+            },
+        }
+    }
+}
+
+/// Filters of a guild, shared with every track it plays so changes apply immediately.
+#[derive(Default)]
+pub struct Filters {
+    settings: Mutex<FilterSettings>,
+    /// Whether tracks are evened out to the same loudness.
+    normalize: AtomicBool,
+}
+
+impl Filters {
+    pub fn get(&self) -> FilterSettings {
+        *self.settings.lock().unwrap()
+    }
+
+    pub fn set(&self, settings: FilterSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    pub fn normalize(&self) -> bool {
+        self.normalize.load(Ordering::Relaxed)
+    }
+
+    pub fn set_normalize(&self, normalize: bool) {
+        self.normalize.store(normalize, Ordering::Relaxed);
+    }
+}
+
+/// How far a filtered stream has played, in time of the source.
+///
+/// Songbird counts the time it played, which the speed filter makes run faster or slower than
+/// the source. Durations, chapters and segments are all in time of the source, so they are
+/// compared against this instead.
+#[derive(Default)]
+pub struct SourceClock(AtomicU64);
+
+impl SourceClock {
+    pub fn get(&self) -> Duration {
+        Duration::from_micros(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, position: Duration) {
+        self.0.store(position.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Wraps a source so its audio runs through the guild's filters.
+///
+/// The inner source is decoded here instead of by songbird, then handed over as raw samples.
+/// Seeking such a stream takes a time of the source rather than a played time.
+pub struct Filtered {
+    inner: Box<dyn Compose>,
+    filters: Arc<Filters>,
+    clock: Arc<SourceClock>,
+    /// Loudness of the source in dB relative to the normalization target, when it is known
+    /// upfront. Otherwise it is measured while playing.
+    loudness: Option<f32>,
+    /// Where the stream starts and ends, in time of the source.
+    start: Duration,
+    end: Option<Duration>,
+}
+
+impl Filtered {
+    pub fn new(
+        inner: Box<dyn Compose>,
+        filters: Arc<Filters>,
+        clock: Arc<SourceClock>,
+        loudness: Option<f32>,
+        start: Duration,
+        end: Option<Duration>,
+    ) -> Self {
+        Filtered {
+            inner,
+            filters,
+            clock,
+            loudness,
+            start,
+            end,
+        }
+    }
+}
+
+#[async_trait]
+impl Compose for Filtered {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = self.inner.create()?;
+        filter_stream(
+            stream,
+            self.filters.clone(),
+            self.clock.clone(),
+            self.loudness,
+            self.start,
+            self.end,
+        )
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = self.inner.create_async().await?;
+        let (filters, clock) = (self.filters.clone(), self.clock.clone());
+        let (loudness, start, end) = (self.loudness, self.start, self.end);
+        // probing reads from the stream, which blocks
+        tokio::task::spawn_blocking(move || {
+            filter_stream(stream, filters, clock, loudness, start, end)
+        })
+        .await
+        .map_err(|e| AudioStreamError::Fail(Box::new(e)))?
+    }
+
+    fn should_create_async(&self) -> bool {
+        self.inner.should_create_async()
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        self.inner.aux_metadata().await
+    }
+}
+
+fn filter_stream(
+    stream: AudioStream<Box<dyn MediaSource>>,
+    filters: Arc<Filters>,
+    clock: Arc<SourceClock>,
+    loudness: Option<f32>,
+    start: Duration,
+    end: Option<Duration>,
+) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+    let mut source = FilteredSource::new(stream, filters, clock, loudness, start, end)?;
+    // decode the first packet here too, so a track opened ahead of time starts without waiting
+    source
+        .fill()
+        .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+    let sample_rate = source.sample_rate;
+    Ok(AudioStream {
+        input: Box::new(RawAdapter::new(source, sample_rate, CHANNELS as u32)),
+        hint: None,
+    })
+}
+
+/// Decodes a stream and filters its samples, reading out interleaved little endian f32 stereo.
+struct FilteredSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    time_base: Option<TimeBase>,
+    /// Seeking never goes before this, so a clip loops and replays from its own start.
+    start: Duration,
+    /// The stream is done once a packet starts at or after this time.
+    end: Option<Duration>,
+    dsp: Dsp,
+    filters: Arc<Filters>,
+    /// Set to the time of each decoded packet.
+    clock: Arc<SourceClock>,
+    /// Filtered bytes which were not read yet.
+    output: Vec<u8>,
+    read: usize,
+    /// Bytes read out so far, which songbird's raw format uses as position.
+    position: u64,
+    done: bool,
+}
+
+impl FilteredSource {
+    fn new(
+        stream: AudioStream<Box<dyn MediaSource>>,
+        filters: Arc<Filters>,
+        clock: Arc<SourceClock>,
+        loudness: Option<f32>,
+        start: Duration,
+        end: Option<Duration>,
+    ) -> Result<Self, AudioStreamError> {
+        let source = MediaSourceStream::new(stream.input, MediaSourceStreamOptions::default());
+        let probed = get_probe()
+            .format(
+                &stream.hint.unwrap_or_default(),
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+        let format = probed.format;
+        let track = format
+            .default_track()
+            .ok_or(AudioStreamError::Fail("No audio track found".into()))?;
+        let track_id = track.id;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(48000);
+        let time_base = track.codec_params.time_base;
+        let decoder = get_codec_registry()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+        clock.set(Duration::ZERO);
+        let mut source = FilteredSource {
+            format,
+            decoder,
+            track_id,
+            sample_rate,
+            time_base,
+            start,
+            end,
+            dsp: Dsp::new(sample_rate as f32, loudness),
+            filters,
+            clock,
+            output: Vec::new(),
+            read: 0,
+            position: 0,
+            done: false,
+        };
+        // seeking here rather than once playing keeps the start of the track from being heard
+        if !start.is_zero() {
+            source
+                .seek_to(start)
+                .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+        }
+        Ok(source)
+    }
+
+    /// Moves the stream to a time of the source, but never before its start.
+    fn seek_to(&mut self, time: Duration) -> io::Result<()> {
+        let time = time.max(self.start);
+        self.format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::new(time.as_secs(), f64::from(time.subsec_nanos()) / 1e9),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(io::Error::other)?;
+
+        self.decoder.reset();
+        self.dsp.reset();
+        self.clock.set(time);
+        self.output.clear();
+        self.read = 0;
+        self.position = (time.as_secs_f64() * f64::from(self.sample_rate)) as u64 * FRAME_BYTES;
+        self.done = false;
+        Ok(())
+    }
+
+    /// Decodes and filters the next packet into `output`.
+    fn fill(&mut self) -> io::Result<()> {
+        let frames = loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(why)) if why.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.done = true;
+                    return Ok(());
+                }
+                Err(why) => return Err(io::Error::other(why)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            if let Some(time_base) = self.time_base {
+                let time = time_base.calc_time(packet.ts());
+                let time = Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac);
+                if self.end.is_some_and(|end| time >= end) {
+                    self.done = true;
+                    return Ok(());
+                }
+                self.clock.set(time);
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // a corrupt packet is only a short glitch, keep going
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(why) => return Err(io::Error::other(why)),
+            };
+
+            let spec = *decoded.spec();
+            let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            samples.copy_interleaved_ref(decoded);
+            break to_stereo(samples.samples(), spec.channels);
+        };
+
+        let filtered = self
+            .dsp
+            .process(frames, self.filters.get(), self.filters.normalize());
+        self.output.clear();
+        self.read = 0;
+        for [left, right] in filtered {
+            self.output.extend_from_slice(&left.to_le_bytes());
+            self.output.extend_from_slice(&right.to_le_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl Read for FilteredSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.output.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+
+        let len = buf.len().min(self.output.len() - self.read);
+        buf[..len].copy_from_slice(&self.output[self.read..self.read + len]);
+        self.read += len;
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for FilteredSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(target) => target,
+            SeekFrom::Current(0) => return Ok(self.position),
+            SeekFrom::Current(offset) => self.position.saturating_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "filtered streams have no known end",
+                ))
+            }
+        };
+
+        // songbird turns the time it seeks to into bytes at our sample rate, this turns them back
+        // and takes the time as one of the source, see [`SourceClock`]
+        let frame = target / FRAME_BYTES;
+        self.seek_to(Duration::from_secs_f64(
+            frame as f64 / f64::from(self.sample_rate),
+        ))?;
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for FilteredSource {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Turns interleaved samples with any number of channels into stereo frames.
+fn to_stereo(samples: &[f32], channels: Channels) -> Vec<[f32; 2]> {
+    match channels.count() {
+        0 => Vec::new(),
+        1 => samples.iter().map(|&sample| [sample, sample]).collect(),
+        2 => samples
+            .chunks_exact(2)
+            .map(|frame| [frame[0], frame[1]])
+            .collect(),
+        count => {
+            // samples are interleaved in the order of the channel bits
+            let weights: Vec<_> = channels.iter().map(downmix_weights).collect();
+            // scale down by the total weight of each side so a loud surround mix does not clip
+            let total = weights
+                .iter()
+                .fold([0.0f32; 2], |[left, right], weight| {
+                    [left + weight[0], right + weight[1]]
+                })
+                .map(|total| total.max(1.0));
+            samples
+                .chunks_exact(count)
+                .map(|frame| {
+                    let mut mixed = [0.0; 2];
+                    for (sample, weight) in frame.iter().zip(&weights) {
+                        mixed[0] += sample * weight[0];
+                        mixed[1] += sample * weight[1];
+                    }
+                    [mixed[0] / total[0], mixed[1] / total[1]]
+                })
+                .collect()
+        }
+    }
+}
+
+/// How much of a channel goes to the left and right side when mixing it down to stereo.
+///
+/// Left and right channels stay on their side, centered ones go to both at -3 dB and the
+/// low frequency effects are left out like most downmixes do.
+fn downmix_weights(channel: Channels) -> [f32; 2] {
+    let left = Channels::FRONT_LEFT
+        | Channels::REAR_LEFT
+        | Channels::FRONT_LEFT_CENTRE
+        | Channels::SIDE_LEFT
+        | Channels::TOP_FRONT_LEFT
+        | Channels::TOP_REAR_LEFT
+        | Channels::REAR_LEFT_CENTRE
+        | Channels::FRONT_LEFT_WIDE
+        | Channels::FRONT_LEFT_HIGH;
+    let right = Channels::FRONT_RIGHT
+        | Channels::REAR_RIGHT
+        | Channels::FRONT_RIGHT_CENTRE
+        | Channels::SIDE_RIGHT
+        | Channels::TOP_FRONT_RIGHT
+        | Channels::TOP_REAR_RIGHT
+        | Channels::REAR_RIGHT_CENTRE
+        | Channels::FRONT_RIGHT_WIDE
+        | Channels::FRONT_RIGHT_HIGH;
+
+    if left.contains(channel) {
+        [1.0, 0.0]
+    } else if right.contains(channel) {
+        [0.0, 1.0]
+    } else if (Channels::LFE1 | Channels::LFE2).contains(channel) {
+        [0.0, 0.0]
+    } else {
+        [FRAC_1_SQRT_2, FRAC_1_SQRT_2]
+    }
+}
+
+/// State of the filters of one stream, kept between packets so they run seamlessly.
+struct Dsp {
+    sample_rate: f32,
+    /// Settings the shelves were computed for.
+    settings: FilterSettings,
+    bass: [Biquad; CHANNELS],
+    treble: [Biquad; CHANNELS],
+    /// Frames waiting to be resampled, along with the fractional position between them.
+    pending: Vec<[f32; 2]>,
+    offset: f64,
+    /// Phase of the panning oscillator in radians.
+    phase: f32,
+    normalizer: Normalizer,
+}
+
+impl Dsp {
+    fn new(sample_rate: f32, loudness: Option<f32>) -> Self {
+        Dsp {
+            sample_rate,
+            settings: FilterSettings::default(),
+            bass: [Biquad::default(); CHANNELS],
+            treble: [Biquad::default(); CHANNELS],
+            pending: Vec::new(),
+            offset: 0.0,
+            phase: 0.0,
+            normalizer: Normalizer::new(sample_rate, loudness),
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in self.bass.iter_mut().chain(&mut self.treble) {
+            filter.clear();
+        }
+        self.pending.clear();
+        self.offset = 0.0;
+        self.normalizer.reset();
+    }
+
+    fn process(
+        &mut self,
+        mut frames: Vec<[f32; 2]>,
+        settings: FilterSettings,
+        normalize: bool,
+    ) -> Vec<[f32; 2]> {
+        // normalize first, so the measurement is of the track itself and not of the filters
+        self.normalizer.process(&mut frames, normalize);
+
+        if settings == FilterSettings::default() && self.pending.is_empty() {
+            self.settings = settings;
+            return frames;
+        }
+
+        if settings.bass != self.settings.bass || settings.treble != self.settings.treble {
+            let bass = Biquad::low_shelf(self.sample_rate, BASS_HZ, settings.bass);
+            let treble = Biquad::high_shelf(self.sample_rate, TREBLE_HZ, settings.treble);
+            for channel in 0..CHANNELS {
+                self.bass[channel].set_coefficients(&bass);
+                self.treble[channel].set_coefficients(&treble);
+            }
+        }
+        self.settings = settings;
+
+        for [left, right] in frames {
+            let [mut left, mut right] = if settings.karaoke {
+                // the side signal is what differs between the channels, centered vocals cancel out
+                let side = (left - right) / 2.0;
+                [side, side]
+            } else {
+                [left, right]
+            };
+            if settings.bass != 0.0 {
+                left = self.bass[0].process(left);
+                right = self.bass[1].process(right);
+            }
+            if settings.treble != 0.0 {
+                left = self.treble[0].process(left);
+                right = self.treble[1].process(right);
+            }
+            self.pending.push([left, right]);
+        }
+
+        let mut output = self.resample(f64::from(settings.speed.max(0.1)));
+
+        if settings.rotation > 0.0 {
+            let step = 2.0 * PI * settings.rotation / self.sample_rate;
+            for frame in &mut output {
+                let [left, right] = pan_gains(self.phase.sin());
+                frame[0] *= left;
+                frame[1] *= right;
+                self.phase = (self.phase + step) % (2.0 * PI);
+            }
+        }
+        output
+    }
+
+    /// Plays the pending frames back at a different rate, interpolating between them.
+    fn resample(&mut self, speed: f64) -> Vec<[f32; 2]> {
+        if speed == 1.0 && self.offset == 0.0 {
+            return std::mem::take(&mut self.pending);
+        }
+
+        let mut output = Vec::with_capacity((self.pending.len() as f64 / speed) as usize + 1);
+        while (self.offset as usize) + 1 < self.pending.len() {
+            let index = self.offset as usize;
+            let fraction = (self.offset - index as f64) as f32;
+            let [a, b] = [self.pending[index], self.pending[index + 1]];
+            output.push([
+                a[0] + (b[0] - a[0]) * fraction,
+                a[1] + (b[1] - a[1]) * fraction,
+            ]);
+            self.offset += speed;
+        }
+
+        let consumed = (self.offset as usize).min(self.pending.len());
+        self.pending.drain(..consumed);
+        self.offset -= consumed as f64;
+        output
+    }
+}
+
+/// Gains of the left and right channel for a pan from -1 (left) to 1 (right).
+///
+/// The power of both together stays the same, so the sound does not get quieter in the middle.
+fn pan_gains(pan: f32) -> [f32; 2] {
+    [
+        ((1.0 - pan) / 2.0).sqrt() * SQRT_2,
+        ((1.0 + pan) / 2.0).sqrt() * SQRT_2,
+    ]
+}
+
+/// Measures the loudness of a stream like EBU R128 does and evens it out toward
+/// [`TARGET_LOUDNESS`].
+///
+/// Blocks do not overlap as in the standard, which makes little difference over a whole track.
+struct Normalizer {
+    frames_per_block: usize,
+    /// K-weighting, a high shelf modelling the head followed by a high pass.
+    shelf: [Biquad; CHANNELS],
+    high_pass: [Biquad; CHANNELS],
+    /// Sum of the weighted power of the frames in the current block.
+    block_power: f64,
+    block_frames: usize,
+    /// Mean power of the last measured blocks.
+    blocks: VecDeque<f64>,
+    /// Whether the loudness was known upfront, so nothing has to be measured.
+    known: bool,
+    /// Gain the stream needs in dB, once it is known or enough was measured.
+    target: Option<f32>,
+    /// Gain applied right now in dB, which slowly follows the target to not jump audibly.
+    gain: f32,
+    /// Change of the gain per frame.
+    step: f32,
+    started: bool,
+}
+
+impl Normalizer {
+    fn new(sample_rate: f32, loudness: Option<f32>) -> Self {
+        let shelf = Biquad::head_shelf(sample_rate);
+        let high_pass = Biquad::loudness_high_pass(sample_rate);
+        Normalizer {
+            frames_per_block: (sample_rate * BLOCK_SECONDS) as usize,
+            shelf: [shelf; CHANNELS],
+            high_pass: [high_pass; CHANNELS],
+            block_power: 0.0,
+            block_frames: 0,
+            blocks: VecDeque::new(),
+            known: loudness.is_some(),
+            target: loudness.map(|loudness| (-loudness).clamp(MIN_GAIN, MAX_GAIN)),
+            gain: 0.0,
+            step: GAIN_SLEW / sample_rate,
+            started: false,
+        }
+    }
+
+    /// Clears the filter history after a seek, the measurement stays as it is the same track.
+    fn reset(&mut self) {
+        for filter in self.shelf.iter_mut().chain(&mut self.high_pass) {
+            filter.clear();
+        }
+        self.block_power = 0.0;
+        self.block_frames = 0;
+    }
+
+    fn process(&mut self, frames: &mut [[f32; 2]], enabled: bool) {
+        if !self.started {
+            self.started = true;
+            // a known loudness applies from the first frame instead of fading in
+            if enabled && self.known {
+                self.gain = self.target.unwrap_or_default();
+            }
+        }
+        if !enabled && self.gain == 0.0 {
+            return;
+        }
+
+        for frame in frames {
+            if enabled && !self.known {
+                self.measure(*frame);
+            }
+            let target = if enabled {
+                self.target.unwrap_or_default()
+            } else {
+                0.0
+            };
+            self.gain += (target - self.gain).clamp(-self.step, self.step);
+
+            let gain = 10f32.powf(self.gain / 20.0);
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+    }
+
+    fn measure(&mut self, frame: [f32; 2]) {
+        for (channel, sample) in frame.into_iter().enumerate() {
+            let weighted = self.high_pass[channel].process(self.shelf[channel].process(sample));
+            self.block_power += f64::from(weighted * weighted);
+        }
+        self.block_frames += 1;
+        if self.block_frames < self.frames_per_block {
+            return;
+        }
+
+        self.blocks
+            .push_back(self.block_power / self.block_frames as f64);
+        if self.blocks.len() > MEASURED_BLOCKS {
+            self.blocks.pop_front();
+        }
+        self.block_power = 0.0;
+        self.block_frames = 0;
+
+        if self.blocks.len() >= SETTLE_BLOCKS {
+            if let Some(loudness) = integrated_loudness(&self.blocks) {
+                let gain = (TARGET_LOUDNESS - loudness) as f32;
+                self.target = Some(gain.clamp(MIN_GAIN, MAX_GAIN));
+            }
+        }
+    }
+}
+
+/// Loudness of the blocks in LUFS, gated so silence and quiet passages do not drag it down.
+fn integrated_loudness(blocks: &VecDeque<f64>) -> Option<f64> {
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean_above = |gate: f64| {
+        let (sum, count) = blocks
+            .iter()
+            .filter(|&&power| loudness(power) > gate)
+            .fold((0.0, 0), |(sum, count), power| (sum + power, count + 1));
+        if count == 0 {
+            None
+        } else {
+            Some(sum / f64::from(count))
+        }
+    };
+
+    let ungated = mean_above(ABSOLUTE_GATE)?;
+    // the relative gate sits 10 LU below the loudness of everything above the absolute gate
+    let gated = mean_above(loudness(ungated) - 10.0)?;
+    Some(loudness(gated))
+}
+
+/// Second order filter, with coefficients from the audio EQ cookbook.
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    fn low_shelf(sample_rate: f32, frequency: f32, gain: f32) -> Self {
+        Self::shelf(sample_rate, frequency, gain, false)
+    }
+
+    fn high_shelf(sample_rate: f32, frequency: f32, gain: f32) -> Self {
+        Self::shelf(sample_rate, frequency, gain, true)
+    }
+
+    fn shelf(sample_rate: f32, frequency: f32, gain: f32, high: bool) -> Self {
+        let a = 10f32.powf(gain / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        // shelf slope of 1
+        let alpha = sin / 2.0 * SQRT_2;
+        let root = 2.0 * a.sqrt() * alpha;
+        // the high shelf is the low shelf mirrored around half the sample rate
+        let cos = if high { -cos } else { cos };
+
+        let b0 = a * ((a + 1.0) - (a - 1.0) * cos + root);
+        let b1 = 2.0 * a * ((a - 1.0) - (a + 1.0) * cos);
+        let b2 = a * ((a + 1.0) - (a - 1.0) * cos - root);
+        let a0 = (a + 1.0) + (a - 1.0) * cos + root;
+        let a1 = -2.0 * ((a - 1.0) + (a + 1.0) * cos);
+        let a2 = (a + 1.0) + (a - 1.0) * cos - root;
+
+        let sign = if high { -1.0 } else { 1.0 };
+        Biquad {
+            b0: b0 / a0,
+            b1: sign * b1 / a0,
+            b2: b2 / a0,
+            a1: sign * a1 / a0,
+            a2: a2 / a0,
+            ..Biquad::default()
+        }
+    }
+
+    /// The high shelf of the K-weighting, which models the acoustic effect of the head.
+    ///
+    /// Its shape differs from the cookbook shelves, the constants are the ones libebur128 derives
+    /// the ITU-R BS.1770 coefficients from for any sample rate.
+    fn head_shelf(sample_rate: f32) -> Self {
+        let k = (std::f64::consts::PI * 1681.974450955533 / f64::from(sample_rate)).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b0: ((vh + vb * k / q + k * k) / a0) as f32,
+            b1: (2.0 * (k * k - vh) / a0) as f32,
+            b2: ((vh - vb * k / q + k * k) / a0) as f32,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+            ..Biquad::default()
+        }
+    }
+
+    /// The high pass of the K-weighting, which leaves out what is too low to be heard as loud.
+    fn loudness_high_pass(sample_rate: f32) -> Self {
+        let k = (std::f64::consts::PI * 38.13547087602444 / f64::from(sample_rate)).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+            ..Biquad::default()
+        }
+    }
+
+    /// Takes over the coefficients of another filter while keeping this one's history.
+    fn set_coefficients(&mut self, other: &Biquad) {
+        self.b0 = other.b0;
+        self.b1 = other.b1;
+        self.b2 = other.b2;
+        self.a1 = other.a1;
+        self.a2 = other.a2;
+    }
+
+    fn clear(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn decibels(gain: f32) -> f32 {
+        20.0 * gain.log10()
+    }
+
+    /// Gain of a filter once it settled, for a constant signal or one alternating at the
+    /// highest frequency there is.
+    fn settled_gain(mut filter: Biquad, nyquist: bool) -> f32 {
+        let mut output = 0.0;
+        for i in 0..SAMPLE_RATE as usize {
+            let input = if nyquist && i % 2 == 1 { -1.0 } else { 1.0 };
+            output = filter.process(input);
+        }
+        output.abs()
+    }
+
+    fn sine(frequency: f32, amplitude: f32, frames: usize) -> Vec<[f32; 2]> {
+        (0..frames)
+            .map(|i| {
+                let sample = amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE).sin();
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shelves_only_change_their_end() {
+        let bass = Biquad::low_shelf(SAMPLE_RATE, BASS_HZ, 6.0);
+        assert!((decibels(settled_gain(bass, false)) - 6.0).abs() < 0.01);
+        assert!(decibels(settled_gain(bass, true)).abs() < 0.01);
+
+        let treble = Biquad::high_shelf(SAMPLE_RATE, TREBLE_HZ, -6.0);
+        assert!(decibels(settled_gain(treble, false)).abs() < 0.01);
+        assert!((decibels(settled_gain(treble, true)) + 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn resampling_changes_the_length_by_the_speed() {
+        for (speed, expected) in [(2.0, 500), (0.5, 2000), (1.25, 800)] {
+            let mut dsp = Dsp::new(SAMPLE_RATE, None);
+            let settings = FilterSettings {
+                speed,
+                ..FilterSettings::default()
+            };
+            let output = dsp.process(sine(440.0, 0.5, 1000), settings, false);
+            assert!(
+                output.len().abs_diff(expected) <= 2,
+                "{} frames at speed {speed}",
+                output.len()
+            );
+        }
+    }
+
+    #[test]
+    fn karaoke_cancels_the_center() {
+        let mut dsp = Dsp::new(SAMPLE_RATE, None);
+        let settings = FilterSettings::from(FilterPreset::Karaoke);
+        let output = dsp.process(sine(440.0, 0.5, 1000), settings, false);
+        assert!(output.iter().flatten().all(|sample| sample.abs() < 1e-6));
+
+        let output = dsp.process(vec![[0.5, -0.5]; 10], settings, false);
+        assert!(output.iter().all(|&frame| frame == [0.5, 0.5]));
+    }
+
+    #[test]
+    fn panning_keeps_the_power() {
+        let [left, right] = pan_gains(0.0);
+        assert!((left - 1.0).abs() < 1e-6 && left == right);
+        assert!(pan_gains(-1.0)[1].abs() < 1e-6);
+        for i in -10..=10 {
+            let [left, right] = pan_gains(i as f32 / 10.0);
+            assert!((left * left + right * right - 2.0).abs() < 1e-5);
+        }
+    }
+
+    /// Gain the normalizer settles on for a signal, measured over some seconds of it.
+    fn measured_gain(frames: Vec<[f32; 2]>) -> Option<f32> {
+        let mut normalizer = Normalizer::new(SAMPLE_RATE, None);
+        let mut frames = frames;
+        normalizer.process(&mut frames, true);
+        normalizer.target
+    }
+
+    #[test]
+    fn measures_the_loudness_of_a_sine() {
+        // a sine at -20 dBFS in both channels is -20 LUFS
+        let blocks = SETTLE_BLOCKS + 2;
+        let frames = (SAMPLE_RATE * BLOCK_SECONDS) as usize * blocks;
+        let gain = measured_gain(sine(997.0, 0.1, frames)).unwrap();
+        assert!((gain - 6.0).abs() < 0.05, "gain of {gain} dB");
+
+        // too little was measured to follow it yet
+        let frames = (SAMPLE_RATE * BLOCK_SECONDS) as usize * (SETTLE_BLOCKS - 1);
+        assert_eq!(measured_gain(sine(997.0, 0.1, frames)), None);
+    }
+
+    #[test]
+    fn ignores_silence() {
+        let block = (SAMPLE_RATE * BLOCK_SECONDS) as usize;
+        let mut frames = vec![[0.0; 2]; block * (SETTLE_BLOCKS + 2)];
+        let mut normalizer = Normalizer::new(SAMPLE_RATE, None);
+        normalizer.process(&mut frames, true);
+        assert_eq!(normalizer.target, None);
+        assert_eq!(normalizer.gain, 0.0);
+        assert!(frames.iter().flatten().all(|&sample| sample == 0.0));
+
+        // silent blocks fall under the absolute gate and do not drag the loudness down
+        let loud = 10f64.powf((-20.0 + 0.691) / 10.0);
+        let mut blocks = VecDeque::from(vec![loud; 10]);
+        let loudness = integrated_loudness(&blocks).unwrap();
+        assert!((loudness + 20.0).abs() < 1e-9);
+        blocks.extend([0.0; 100]);
+        assert_eq!(integrated_loudness(&blocks), Some(loudness));
+        assert_eq!(integrated_loudness(&VecDeque::from(vec![0.0; 10])), None);
+    }
+
+    #[test]
+    fn clamps_the_gain() {
+        assert_eq!(
+            Normalizer::new(SAMPLE_RATE, Some(-40.0)).target,
+            Some(MAX_GAIN)
+        );
+        assert_eq!(
+            Normalizer::new(SAMPLE_RATE, Some(20.0)).target,
+            Some(MIN_GAIN)
+        );
+        assert_eq!(Normalizer::new(SAMPLE_RATE, Some(-3.0)).target, Some(3.0));
+
+        // a very quiet track is only raised as much as allowed
+        let frames = (SAMPLE_RATE * BLOCK_SECONDS) as usize * (SETTLE_BLOCKS + 2);
+        assert_eq!(measured_gain(sine(997.0, 0.001, frames)), Some(MAX_GAIN));
+
+        // a known loudness applies right away instead of fading in
+        let mut normalizer = Normalizer::new(SAMPLE_RATE, Some(20.0));
+        let mut frames = vec![[1.0; 2]];
+        normalizer.process(&mut frames, true);
+        assert_eq!(normalizer.gain, MIN_GAIN);
+    }
+
+    #[test]
+    fn mixes_down_to_stereo() {
+        assert_eq!(to_stereo(&[0.5], Channels::FRONT_CENTRE), [[0.5, 0.5]]);
+        let stereo = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+        assert_eq!(to_stereo(&[0.5, -0.5], stereo), [[0.5, -0.5]]);
+
+        let surround = stereo
+            | Channels::FRONT_CENTRE
+            | Channels::LFE1
+            | Channels::REAR_LEFT
+            | Channels::REAR_RIGHT;
+        let mix = |frame: [f32; 6]| to_stereo(&frame, surround)[0];
+        // the center and rear channels are kept instead of dropped
+        let [left, right] = mix([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        assert!(left > 0.0 && left == right);
+        let [left, right] = mix([0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert!(left > 0.0 && right == 0.0);
+        assert_eq!(mix([0.0, 0.0, 0.0, 1.0, 0.0, 0.0]), [0.0, 0.0]);
+        // everything at full scale does not clip
+        let [left, right] = mix([1.0; 6]);
+        assert!(left <= 1.0 && right <= 1.0);
+    }
+}
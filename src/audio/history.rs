@@ -0,0 +1,82 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use poise::serenity_prelude as serenity;
+
+use super::clip::Clip;
+use crate::store::Store;
+
+/// How many played tracks are remembered per guild.
+pub const HISTORY_LIMIT: usize = 100;
+
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+    pub requester: String,
+    /// Part of the track which was played, which `/previous` plays again.
+    pub clip: Option<Clip>,
+    pub played_at: SystemTime,
+}
+
+/// Ring buffer of the tracks each guild has played, newest first.
+///
+/// Kept in memory unless a store is given, in which case it survives restarts.
+pub struct History {
+    entries: Mutex<HashMap<serenity::GuildId, VecDeque<HistoryEntry>>>,
+    store: Option<Arc<Store>>,
+}
+
+impl History {
+    pub fn new(store: Option<Arc<Store>>) -> Self {
+        History {
+            entries: Mutex::default(),
+            store,
+        }
+    }
+
+    pub async fn record(&self, guild_id: serenity::GuildId, entry: HistoryEntry) -> Result<()> {
+        if let Some(store) = &self.store {
+            return store
+                .blocking(move |store| store.record_history(guild_id.get(), &entry, HISTORY_LIMIT))
+                .await;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let history = entries.entry(guild_id).or_default();
+        history.push_front(entry);
+        history.truncate(HISTORY_LIMIT);
+        Ok(())
+    }
+
+    /// Returns the played tracks of a guild, newest first.
+    pub async fn recent(&self, guild_id: serenity::GuildId) -> Result<Vec<HistoryEntry>> {
+        if let Some(store) = &self.store {
+            return store
+                .blocking(move |store| store.history(guild_id.get()))
+                .await;
+        }
+
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .get(&guild_id)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Removes and returns the most recently played track of a guild.
+    pub async fn pop(&self, guild_id: serenity::GuildId) -> Result<Option<HistoryEntry>> {
+        if let Some(store) = &self.store {
+            return store
+                .blocking(move |store| store.pop_history(guild_id.get()))
+                .await;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        Ok(entries.get_mut(&guild_id).and_then(VecDeque::pop_front))
+    }
+}
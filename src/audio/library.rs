@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use songbird::input::AuxMetadata;
+use tracing::warn;
+
+use super::{
+    resolver::{Resolved, SourceResolver},
+    sources::{probe_tags, LocalFile},
+};
+
+/// How often the library directory is checked for added, changed or removed files.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(300);
+/// Files with any other extension are left out of the index.
+const EXTENSIONS: [&str; 7] = ["aac", "flac", "m4a", "mp3", "ogg", "opus", "wav"];
+/// Prefix of the links library tracks are enqueued, saved and remembered with.
+const SCHEME: &str = "library:";
+
+#[derive(Clone)]
+pub struct LibraryTrack {
+    /// Path relative to the library directory.
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+    pub has_artwork: bool,
+}
+
+impl LibraryTrack {
+    pub fn url(&self) -> String {
+        format!("{SCHEME}{}", self.path.display())
+    }
+
+    fn metadata(&self) -> AuxMetadata {
+        AuxMetadata {
+            title: Some(self.title.clone()),
+            artist: self.artist.clone(),
+            channel: self.artist.clone(),
+            album: self.album.clone(),
+            duration: self.duration,
+            source_url: Some(self.url()),
+            ..AuxMetadata::default()
+        }
+    }
+
+    /// Whether every word of a query appears somewhere in the track's tags or file name.
+    fn matches(&self, words: &[String]) -> bool {
+        let haystack = format!(
+            "{} {} {} {}",
+            self.title,
+            self.artist.as_deref().unwrap_or_default(),
+            self.album.as_deref().unwrap_or_default(),
+            self.path.display()
+        )
+        .to_lowercase();
+        words.iter().all(|word| haystack.contains(word))
+    }
+}
+
+struct Indexed {
+    modified: SystemTime,
+    track: LibraryTrack,
+}
+
+/// Index of the music files in a directory the server hosts.
+///
+/// Tags are read once per file and only read again when the file's modification time changes.
+pub struct Library {
+    root: PathBuf,
+    index: RwLock<HashMap<PathBuf, Indexed>>,
+}
+
+impl Library {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Library {
+            root: root.into(),
+            index: RwLock::default(),
+        }
+    }
+
+    /// Keeps rescanning the directory in the background.
+    pub fn watch(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                let library = self.clone();
+                match tokio::task::spawn_blocking(move || library.rescan()).await {
+                    Ok(Err(why)) => warn!("could not scan library: {why:?}"),
+                    Err(why) => warn!("library scan panicked: {why:?}"),
+                    Ok(Ok(())) => {}
+                }
+                tokio::time::sleep(RESCAN_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Brings the index up to date, reading tags only for new or changed files.
+    pub fn rescan(&self) -> io::Result<()> {
+        let mut files = Vec::new();
+        collect_files(&self.root, &mut files)?;
+
+        let changed: Vec<_> = {
+            let index = self.index.read().unwrap();
+            files
+                .iter()
+                .filter(|(path, modified)| {
+                    index
+                        .get(path)
+                        .map_or(true, |indexed| indexed.modified != *modified)
+                })
+                .cloned()
+                .collect()
+        };
+
+        // read tags without holding the lock, this is the slow part
+        let probed: Vec<_> = changed
+            .into_iter()
+            .filter_map(|(path, modified)| {
+                let track = self.read_track(&path)?;
+                Some((path, Indexed { modified, track }))
+            })
+            .collect();
+
+        let mut index = self.index.write().unwrap();
+        index.retain(|path, _| files.iter().any(|(file, _)| file == path));
+        index.extend(probed);
+        Ok(())
+    }
+
+    fn read_track(&self, path: &Path) -> Option<LibraryTrack> {
+        let file = match File::open(self.root.join(path)) {
+            Ok(file) => file,
+            Err(why) => {
+                warn!("could not open {}: {why:?}", path.display());
+                return None;
+            }
+        };
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let tags = probe_tags(Box::new(file), extension).unwrap_or_default();
+
+        let stem = path.file_stem()?.to_string_lossy().into_owned();
+        Some(LibraryTrack {
+            path: path.to_owned(),
+            title: tags.title.unwrap_or(stem),
+            artist: tags.artist,
+            album: tags.album,
+            duration: tags.duration,
+            has_artwork: tags.artwork.is_some(),
+        })
+    }
+
+    /// Returns the tracks matching every word of a query, sorted by artist, album and path.
+    pub fn search(&self, query: &str) -> Vec<LibraryTrack> {
+        let words: Vec<_> = query.split_whitespace().map(str::to_lowercase).collect();
+        let index = self.index.read().unwrap();
+        let mut tracks: Vec<_> = index
+            .values()
+            .map(|indexed| &indexed.track)
+            .filter(|track| track.matches(&words))
+            .cloned()
+            .collect();
+        tracks.sort_by(|a, b| (&a.artist, &a.album, &a.path).cmp(&(&b.artist, &b.album, &b.path)));
+        tracks
+    }
+
+    /// Returns the names of albums containing `partial`, sorted.
+    pub fn albums(&self, partial: &str) -> Vec<String> {
+        let partial = partial.to_lowercase();
+        let index = self.index.read().unwrap();
+        let mut albums: Vec<_> = index
+            .values()
+            .filter_map(|indexed| indexed.track.album.clone())
+            .filter(|album| album.to_lowercase().contains(&partial))
+            .collect();
+        albums.sort();
+        albums.dedup();
+        albums
+    }
+
+    /// Returns the tracks of an album in file order, which is usually the track order.
+    pub fn album(&self, name: &str) -> Vec<LibraryTrack> {
+        let index = self.index.read().unwrap();
+        let mut tracks: Vec<_> = index
+            .values()
+            .map(|indexed| &indexed.track)
+            .filter(|track| {
+                track
+                    .album
+                    .as_deref()
+                    .is_some_and(|album| album.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+            .collect();
+        tracks.sort_by(|a, b| a.path.cmp(&b.path));
+        tracks
+    }
+
+    /// Looks up a track by its `library:` link.
+    pub fn get(&self, url: &str) -> Option<LibraryTrack> {
+        let path = Path::new(url.strip_prefix(SCHEME)?);
+        let index = self.index.read().unwrap();
+        index.get(path).map(|indexed| indexed.track.clone())
+    }
+}
+
+/// Collects every audio file below `root`, relative to it, along with its modification time.
+fn collect_files(root: &Path, files: &mut Vec<(PathBuf, SystemTime)>) -> io::Result<()> {
+    let mut dirs = vec![root.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let audio = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if let (true, Ok(relative)) = (audio, path.strip_prefix(root)) {
+                files.push((relative.to_owned(), metadata.modified()?));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves `library:` links into files of the local library.
+pub struct LibraryResolver {
+    library: Arc<Library>,
+}
+
+impl LibraryResolver {
+    pub fn new(library: Arc<Library>) -> Self {
+        LibraryResolver { library }
+    }
+}
+
+#[async_trait]
+impl SourceResolver for LibraryResolver {
+    fn handles(&self, url: &str) -> bool {
+        url.starts_with(SCHEME)
+    }
+
+    async fn resolve(&self, url: &str) -> Result<Resolved> {
+        // only indexed files can be played, so links cannot reach outside the library
+        let track = self
+            .library
+            .get(url)
+            .ok_or_else(|| anyhow!("{url} is not in the library"))?;
+        let path = self.library.root.join(&track.path);
+
+        let artwork = if track.has_artwork {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || {
+                let extension = path.extension().and_then(|ext| ext.to_str());
+                probe_tags(Box::new(File::open(&path).ok()?), extension)?.artwork
+            })
+            .await?
+        } else {
+            None
+        };
+
+        let metadata = track.metadata();
+        Ok(Resolved {
+            input: LocalFile::new(path, metadata.clone()).into(),
+            metadata,
+            live: false,
+            artwork,
+            loudness: None,
+            segments: Vec::new(),
+            clip: None,
+            chapters: Vec::new(),
+        })
+    }
+
+    async fn metadata(&self, url: &str) -> Result<AuxMetadata> {
+        let track = self
+            .library
+            .get(url)
+            .ok_or_else(|| anyhow!("{url} is not in the library"))?;
+        Ok(track.metadata())
+    }
+}
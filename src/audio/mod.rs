@@ -1 +1,12 @@
+pub mod cache;
+pub mod chapters;
+pub mod clip;
+pub mod filters;
+pub mod history;
+pub mod library;
+pub mod resolver;
+pub mod search;
+pub mod segments;
+pub mod session;
 pub mod sources;
+pub mod timestamp;
@@ -0,0 +1,108 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use songbird::input::{AuxMetadata, Input};
+
+use super::{chapters::Chapter, clip::Clip, segments::Segment};
+
+/// A lazy input ready to be enqueued along with its metadata.
+pub struct Resolved {
+    pub input: Input,
+    pub metadata: AuxMetadata,
+    /// Whether the source is a live stream, which plays until it is stopped and cannot seek.
+    pub live: bool,
+    /// Cover art of sources which carry it themselves instead of linking to a thumbnail.
+    pub artwork: Option<Vec<u8>>,
+    /// Loudness in dB relative to the normalization target, for sources which measured it.
+    pub loudness: Option<f32>,
+    /// Parts of the track which are skipped while playing.
+    pub segments: Vec<Segment>,
+    /// Range of the track the link asked for.
+    pub clip: Option<Clip>,
+    pub chapters: Vec<Chapter>,
+}
+
+/// Error of links the bot cannot play, either as no resolver handles them or as they turned out
+/// not to be audio once fetched.
+#[derive(Debug)]
+pub struct UnsupportedLink(pub String);
+
+impl fmt::Display for UnsupportedLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported link {}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedLink {}
+
+/// Turns links of one kind of source into playable tracks.
+#[async_trait]
+pub trait SourceResolver: Send + Sync {
+    /// Whether this resolver understands the link.
+    fn handles(&self, url: &str) -> bool;
+
+    /// Returns the links of every track behind a link, which is more than one for playlists.
+    async fn entries(&self, url: &str) -> Result<Vec<String>> {
+        Ok(vec![url.to_owned()])
+    }
+
+    /// Resolves the link of a single track.
+    async fn resolve(&self, url: &str) -> Result<Resolved>;
+
+    /// Fetches only the metadata of a track, for sources where that is cheaper than resolving.
+    async fn metadata(&self, url: &str) -> Result<AuxMetadata> {
+        Ok(self.resolve(url).await?.metadata)
+    }
+
+    /// Searches for tracks and returns their links, `None` if the source cannot be searched.
+    async fn search(&self, _query: &str) -> Option<Result<Vec<String>>> {
+        None
+    }
+}
+
+/// Every source the bot can play from, tried in the order they were registered.
+pub struct Resolvers {
+    resolvers: Vec<Arc<dyn SourceResolver>>,
+}
+
+impl Resolvers {
+    pub fn new(resolvers: Vec<Arc<dyn SourceResolver>>) -> Self {
+        Resolvers { resolvers }
+    }
+
+    /// Returns the first resolver which handles the link.
+    pub fn find(&self, url: &str) -> Option<Arc<dyn SourceResolver>> {
+        self.resolvers
+            .iter()
+            .find(|resolver| resolver.handles(url))
+            .cloned()
+    }
+
+    /// Resolves the link of a single track with whichever resolver handles it.
+    pub async fn resolve(&self, url: &str) -> Result<Resolved> {
+        let resolver = self
+            .find(url)
+            .ok_or_else(|| UnsupportedLink(url.to_owned()))?;
+        resolver.resolve(url).await
+    }
+
+    /// Fetches the metadata of a single track with whichever resolver handles it.
+    pub async fn metadata(&self, url: &str) -> Result<AuxMetadata> {
+        let resolver = self
+            .find(url)
+            .ok_or_else(|| UnsupportedLink(url.to_owned()))?;
+        resolver.metadata(url).await
+    }
+
+    /// Searches with the first resolver which supports searching.
+    pub async fn search(&self, query: &str) -> Result<Vec<String>> {
+        for resolver in &self.resolvers {
+            if let Some(results) = resolver.search(query).await {
+                return results;
+            }
+        }
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use songbird::input::AuxMetadata;
+
+/// How long search results are reused for the same query.
+const CACHE_TTL: Duration = Duration::from_secs(120);
+/// How long a user has to stop typing before a search is made.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Short lived cache of search results used by autocomplete.
+///
+/// Discord sends an autocomplete request on every keystroke, so requests are debounced per user
+/// and results are kept briefly per query to avoid hitting the extractor for each one.
+#[derive(Default)]
+pub struct SearchCache {
+    entries: Mutex<HashMap<String, (Instant, Arc<Vec<AuxMetadata>>)>>,
+    latest: Mutex<HashMap<u64, u64>>,
+}
+
+impl SearchCache {
+    /// Waits out the debounce period, returning false if the user has typed since.
+    pub async fn debounce(&self, user_id: u64) -> bool {
+        let generation = {
+            let mut latest = self.latest.lock().unwrap();
+            let generation = latest.entry(user_id).or_default();
+            *generation += 1;
+            *generation
+        };
+
+        tokio::time::sleep(DEBOUNCE).await;
+        self.latest.lock().unwrap().get(&user_id) == Some(&generation)
+    }
+
+    pub fn get(&self, query: &str) -> Option<Arc<Vec<AuxMetadata>>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&query.to_lowercase())
+            .filter(|(time, _)| time.elapsed() < CACHE_TTL)
+            .map(|(_, results)| results.clone())
+    }
+
+    pub fn insert(&self, query: &str, results: Vec<AuxMetadata>) -> Arc<Vec<AuxMetadata>> {
+        let results = Arc::new(results);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (time, _)| time.elapsed() < CACHE_TTL);
+        entries.insert(query.to_lowercase(), (Instant::now(), results.clone()));
+        results
+    }
+}
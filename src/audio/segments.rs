@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+/// Public SponsorBlock API, used unless another one is configured.
+pub const DEFAULT_API: &str = "https://sponsor.ajay.app";
+/// Categories of segments which are skipped.
+const CATEGORIES: [&str; 4] = ["music_offtopic", "intro", "outro", "sponsor"];
+/// Segments are submitted by hand, so one ending this close to the end of a video is taken to run
+/// until the end.
+const END_TOLERANCE: Duration = Duration::from_secs(1);
+
+/// A part of a video which is skipped, in time of the video itself.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+#[derive(Deserialize)]
+struct ApiSegment {
+    segment: [f64; 2],
+    #[serde(rename = "actionType")]
+    action_type: String,
+}
+
+/// Client of a SponsorBlock compatible API, which can be a local mirror.
+pub struct SponsorBlock {
+    client: Client,
+    base_url: String,
+}
+
+impl SponsorBlock {
+    pub fn new(client: Client, base_url: impl Into<String>) -> Self {
+        SponsorBlock {
+            client,
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Fetches the segments of a video to skip, sorted and with overlapping ones merged.
+    pub async fn segments(&self, video_id: &str) -> Result<Vec<Segment>> {
+        let categories = serde_json::to_string(&CATEGORIES)?;
+        let response = self
+            .client
+            .get(format!("{}/api/skipSegments", self.base_url))
+            .query(&[("videoID", video_id), ("categories", &categories)])
+            .send()
+            .await?;
+        // videos without any segments are not found
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+
+        let segments = response
+            .error_for_status()?
+            .json::<Vec<ApiSegment>>()
+            .await?
+            .into_iter()
+            // other actions only mute or mark a segment
+            .filter(|segment| segment.action_type == "skip")
+            .filter_map(|segment| {
+                let [start, end] = segment.segment;
+                let start = Duration::try_from_secs_f64(start).ok()?;
+                let end = Duration::try_from_secs_f64(end).ok()?;
+                (start < end).then_some(Segment { start, end })
+            })
+            .collect();
+        Ok(merge(segments))
+    }
+}
+
+/// Sorts segments and merges the ones which overlap or touch.
+fn merge(mut segments: Vec<Segment>) -> Vec<Segment> {
+    segments.sort_by_key(|segment| segment.start);
+
+    let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        match merged.last_mut() {
+            Some(last) if segment.start <= last.end => last.end = last.end.max(segment.end),
+            _ => merged.push(segment),
+        }
+    }
+    merged
+}
+
+/// Where a segment which runs until the end of a track starts, as there is nothing to skip to.
+pub fn outro_start(segments: &[Segment], duration: Duration) -> Option<Duration> {
+    segments
+        .last()
+        .filter(|segment| segment.end + END_TOLERANCE >= duration)
+        .map(|segment| segment.start)
+}
+
+/// Where playback continues when `position` lies within a segment.
+pub fn segment_end(segments: &[Segment], position: Duration) -> Option<Duration> {
+    segments
+        .iter()
+        .find(|segment| segment.start <= position && position < segment.end)
+        .map(|segment| segment.end)
+}
+
+/// Time left to play from `position` until `duration`, leaving out the skipped segments.
+pub fn time_left(duration: Duration, position: Duration, segments: &[Segment]) -> Duration {
+    let skipped: Duration = segments
+        .iter()
+        .map(|segment| {
+            let start = segment.start.max(position);
+            let end = segment.end.min(duration);
+            end.saturating_sub(start)
+        })
+        .sum();
+    duration.saturating_sub(position).saturating_sub(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: u64, end: u64) -> Segment {
+        Segment {
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+        }
+    }
+
+    fn secs(segments: &[Segment]) -> Vec<(u64, u64)> {
+        segments
+            .iter()
+            .map(|segment| (segment.start.as_secs(), segment.end.as_secs()))
+            .collect()
+    }
+
+    #[test]
+    fn merges_overlapping_segments() {
+        let merged = merge(vec![
+            segment(50, 60),
+            segment(0, 10),
+            segment(5, 20),
+            segment(20, 30),
+            segment(55, 58),
+        ]);
+        assert_eq!(secs(&merged), [(0, 30), (50, 60)]);
+    }
+
+    #[test]
+    fn time_left_skips_segments_ahead() {
+        let duration = Duration::from_secs(100);
+        let segments = [segment(10, 20), segment(50, 60)];
+        let left = |position| time_left(duration, Duration::from_secs(position), &segments);
+        assert_eq!(left(0), Duration::from_secs(80));
+        // only the rest of a segment which is playing counts
+        assert_eq!(left(15), Duration::from_secs(70));
+        assert_eq!(left(30), Duration::from_secs(60));
+        assert_eq!(left(100), Duration::ZERO);
+        assert_eq!(left(120), Duration::ZERO);
+        // a clip ending early leaves out what comes after
+        let end = Duration::from_secs(55);
+        assert_eq!(
+            time_left(end, Duration::ZERO, &segments),
+            Duration::from_secs(40)
+        );
+    }
+
+    #[test]
+    fn finds_outros() {
+        let duration = Duration::from_secs(100);
+        let outro = |segments: &[Segment]| outro_start(segments, duration);
+        assert_eq!(
+            outro(&[segment(10, 20), segment(90, 100)]),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(outro(&[segment(90, 99)]), Some(Duration::from_secs(90)));
+        assert_eq!(outro(&[segment(80, 90)]), None);
+        assert_eq!(outro(&[]), None);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::FRAC_PI_2,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::filters::Filters;
+
+/// What happens to a track once it finishes playing.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// The finished track plays again.
+    Track,
+    /// Finished tracks are added back to the end of the queue.
+    Queue,
+    /// Finished tracks are added back at a random upcoming position.
+    #[name = "Shuffle Repeat"]
+    ShuffleRepeat,
+}
+
+impl RepeatMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "Off",
+            RepeatMode::Track => "Track",
+            RepeatMode::Queue => "Queue",
+            RepeatMode::ShuffleRepeat => "Shuffle Repeat",
+        }
+    }
+
+    /// The mode after this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::Track,
+            RepeatMode::Track => RepeatMode::Queue,
+            RepeatMode::Queue => RepeatMode::ShuffleRepeat,
+            RepeatMode::ShuffleRepeat => RepeatMode::Off,
+        }
+    }
+}
+
+/// How the volumes of two tracks change while they crossfade.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum FadeCurve {
+    /// Both volumes change at a constant rate, which dips in loudness halfway through.
+    Linear,
+    /// Keeps the combined loudness steady throughout the fade.
+    #[default]
+    #[name = "Equal Power"]
+    EqualPower,
+    /// Changes slowly at the start and end of the fade and quickly in between.
+    #[name = "S-Curve"]
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Volumes of the outgoing and incoming track, `progress` going from 0 to 1 over the fade.
+    pub fn gains(self, progress: f32) -> (f32, f32) {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => (1.0 - t, t),
+            FadeCurve::EqualPower => ((t * FRAC_PI_2).cos(), (t * FRAC_PI_2).sin()),
+            FadeCurve::SCurve => {
+                let eased = t * t * (3.0 - 2.0 * t);
+                (1.0 - eased, eased)
+            }
+        }
+    }
+}
+
+/// Playback state of a guild which lives as long as the bot is in a voice channel there.
+pub struct Session {
+    /// Volume applied to the current and every newly enqueued track, 1.0 being unchanged.
+    pub volume: f32,
+    pub repeat: RepeatMode,
+    /// Whether related tracks are enqueued once the queue runs out.
+    pub autoplay: bool,
+    /// Audio filters shared with every track of the session.
+    pub filters: Arc<Filters>,
+    /// How long tracks fade into each other, zero for hard cuts.
+    pub crossfade: Duration,
+    pub fade_curve: FadeCurve,
+    /// Channel where music was requested, used for notices.
+    pub text_channel: Option<serenity::ChannelId>,
+    /// When the last listener left the bot's voice channel.
+    pub alone_since: Option<Instant>,
+    /// When the queue ran out of tracks.
+    pub idle_since: Option<Instant>,
+    /// Users who voted to skip, along with the track they voted on.
+    pub skip_votes: (Option<Uuid>, HashSet<serenity::UserId>),
+    /// Message showing the current track with playback controls.
+    pub player_message: Option<(serenity::ChannelId, serenity::MessageId)>,
+    /// Track which is left out of the history when it ends, as `/previous` put it back.
+    pub unrecorded: Option<Uuid>,
+    /// How many tracks failed in a row.
+    pub failures: u8,
+    /// Copy of a failed track which is not retried again if it fails as well.
+    pub retried: Option<Uuid>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            volume: 1.0,
+            repeat: RepeatMode::Off,
+            autoplay: false,
+            filters: Arc::default(),
+            crossfade: Duration::ZERO,
+            fade_curve: FadeCurve::default(),
+            text_channel: None,
+            alone_since: None,
+            idle_since: None,
+            skip_votes: (None, HashSet::new()),
+            player_message: None,
+            unrecorded: None,
+            failures: 0,
+            retried: None,
+        }
+    }
+}
+
+/// Sessions of every guild the bot is currently playing in.
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<serenity::GuildId, Session>>,
+}
+
+impl Sessions {
+    pub fn start(&self, guild_id: serenity::GuildId, session: Session) {
+        self.sessions.lock().unwrap().insert(guild_id, session);
+    }
+
+    pub fn end(&self, guild_id: serenity::GuildId) {
+        self.sessions.lock().unwrap().remove(&guild_id);
+    }
+
+    /// Runs `f` on the session of a guild, starting a default one if there is none.
+    pub fn with<T>(&self, guild_id: serenity::GuildId, f: impl FnOnce(&mut Session) -> T) -> T {
+        let mut sessions = self.sessions.lock().unwrap();
+        f(sessions.entry(guild_id).or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6
+    }
+
+    #[test]
+    fn fades_start_on_the_outgoing_track_and_end_on_the_incoming_one() {
+        for curve in [FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::SCurve] {
+            assert!(close(curve.gains(0.0), (1.0, 0.0)), "{curve:?}");
+            assert!(close(curve.gains(1.0), (0.0, 1.0)), "{curve:?}");
+            // progress is clamped, so a late or early tick does not overshoot
+            assert!(close(curve.gains(-0.5), (1.0, 0.0)), "{curve:?}");
+            assert!(close(curve.gains(1.5), (0.0, 1.0)), "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn fade_curves_differ_halfway() {
+        assert!(close(FadeCurve::Linear.gains(0.5), (0.5, 0.5)));
+        assert!(close(FadeCurve::SCurve.gains(0.5), (0.5, 0.5)));
+        let (outgoing, incoming) = FadeCurve::EqualPower.gains(0.5);
+        assert!((outgoing * outgoing + incoming * incoming - 1.0).abs() < 1e-6);
+        assert!(outgoing > 0.5 && incoming > 0.5);
+        // the s-curve barely moves at the start and end
+        let (_, early) = FadeCurve::SCurve.gains(0.1);
+        assert!(early < FadeCurve::Linear.gains(0.1).1);
+    }
+}
@@ -1,14 +1,43 @@
-use std::time::Duration;
+use std::{
+    fs::File,
+    io::Cursor,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
 
 use songbird::input::{AudioStream, AudioStreamError, AuxMetadata, Compose, HttpRequest, Input};
+use symphonia::core::{
+    formats::FormatOptions,
+    io::{MediaSourceStream, MediaSourceStreamOptions},
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint,
+};
 use symphonia_core::io::MediaSource;
 
-use reqwest::{header::HeaderMap, Client};
+use reqwest::{
+    dns::{Addrs, Name, Resolving},
+    header::{HeaderMap, CONTENT_TYPE},
+    redirect, Client,
+};
 
+use tracing::warn;
 use yinfo::{structs::VideoDetails, Innertube};
 
+use super::{
+    cache::{AudioCache, CachingSource},
+    chapters::{parse_chapters, Chapter},
+    clip::Clip,
+    resolver::{Resolved, SourceResolver, UnsupportedLink},
+    segments::SponsorBlock,
+};
+
+/// Deciphered stream urls expire after about six hours, refresh them a bit before that.
+const STREAM_URL_LIFETIME: Duration = Duration::from_secs(5 * 60 * 60);
+
 /// A similar struct to [`songbird::input::YoutubeDl`], though only for YouTube links.
 ///
 /// However there are some differences. Calling [`YouTube::new()`] immediately creates a request to
@@ -17,49 +46,140 @@ use yinfo::{structs::VideoDetails, Innertube};
 /// when we reach it in the queue.
 ///
 /// This also means metadata is guaranteed since it is extracted during the initial request.
+///
+/// Tracks far down the queue may only be played hours later, so the stream url is deciphered
+/// again when it is old or the stream cannot be opened with it anymore.
 pub struct YouTube {
+    innertube: Arc<Innertube>,
     client: Client,
+    /// Video url, kept to decipher a fresh stream url.
+    url: String,
+    video_id: String,
     metadata: AuxMetadata,
+    /// Chapters from the video's description.
+    chapters: Vec<Chapter>,
+    stream: Stream,
+    /// When the stream url was deciphered.
+    fetched_at: Instant,
+    cache: Option<Arc<AudioCache>>,
+}
+
+/// The audio format of a video picked for playback.
+struct Stream {
+    url: String,
     file_size: Option<String>,
-    stream_url: String,
+    /// Id of the format, streams of the same video differ in it.
+    itag: String,
+    /// Loudness YouTube measured, in dB above its reference level.
+    loudness: Option<f32>,
 }
 
 impl YouTube {
     /// Creates a new YouTube source for the given video id or url.
     ///
-    /// The request to the extracted stream url uses the passed in client.
+    /// The request to the extracted stream url uses the passed in client. Streams are played from
+    /// the cache when it has them and written to it otherwise.
     pub async fn new(
-        innertube: &Innertube,
+        innertube: Arc<Innertube>,
         client: Client,
+        cache: Option<Arc<AudioCache>>,
         url: &str,
     ) -> Result<Self, AudioStreamError> {
-        let video = innertube
-            .info(url)
-            .await
-            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+        let (details, stream) = fetch_stream(&innertube, url).await?;
+
+        Ok(YouTube {
+            innertube,
+            client,
+            url: url.to_owned(),
+            video_id: details.video_id.clone(),
+            chapters: parse_chapters(&details.short_description),
+            metadata: details_to_metadata(details),
+            stream,
+            fetched_at: Instant::now(),
+            cache,
+        })
+    }
+
+    pub fn video_id(&self) -> &str {
+        &self.video_id
+    }
+
+    pub fn loudness(&self) -> Option<f32> {
+        self.stream.loudness
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    async fn refresh(&mut self) -> Result<(), AudioStreamError> {
+        let (_, stream) = fetch_stream(&self.innertube, &self.url).await?;
+        self.stream = stream;
+        self.fetched_at = Instant::now();
+        Ok(())
+    }
+
+    fn request(&self) -> HttpRequest {
+        let content_length = self
+            .stream
+            .file_size
+            .as_ref()
+            .map(|s| s.parse::<u64>().unwrap());
+
+        HttpRequest {
+            client: self.client.clone(),
+            request: self.stream.url.clone(),
+            headers: HeaderMap::default(),
+            content_length,
+        }
+    }
 
-        if video.playability_status.status != "OK" {
-            return Err(AudioStreamError::Fail("Video is unavailable.".into()));
+    async fn download(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        if self.fetched_at.elapsed() > STREAM_URL_LIFETIME {
+            self.refresh().await?;
         }
 
-        let format = video
-            .best_audio()
-            .ok_or(AudioStreamError::Fail("No formats found".into()))?;
-        let stream_url = innertube
-            .decipher_format(format)
-            .await
-            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+        match self.request().create_async().await {
+            // expired urls get a 403, which songbird only reports as a failure message
+            Err(AudioStreamError::Fail(why)) => {
+                warn!("refreshing stream url of {} after: {why}", self.url);
+                self.refresh().await?;
+                self.request().create_async().await
+            }
+            result => result,
+        }
+    }
+}
 
-        let file_size = format.content_length.clone();
-        let metadata = details_to_metadata(video.video_details);
+/// Fetches the details of a video along with a freshly deciphered stream of it.
+async fn fetch_stream(
+    innertube: &Innertube,
+    url: &str,
+) -> Result<(VideoDetails, Stream), AudioStreamError> {
+    let video = innertube
+        .info(url)
+        .await
+        .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
 
-        Ok(YouTube {
-            client,
-            metadata,
-            file_size,
-            stream_url,
-        })
+    if video.playability_status.status != "OK" {
+        return Err(AudioStreamError::Fail("Video is unavailable.".into()));
     }
+
+    let format = video
+        .best_audio()
+        .ok_or(AudioStreamError::Fail("No formats found".into()))?;
+    let stream_url = innertube
+        .decipher_format(format)
+        .await
+        .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+    let stream = Stream {
+        url: stream_url,
+        file_size: format.content_length.clone(),
+        itag: format.itag.to_string(),
+        loudness: format.loudness_db.map(|db| db as f32),
+    };
+    Ok((video.video_details, stream))
 }
 
 impl From<YouTube> for Input {
@@ -77,13 +197,240 @@ impl Compose for YouTube {
     async fn create_async(
         &mut self,
     ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
-        let content_length = self.file_size.as_ref().map(|s| s.parse::<u64>().unwrap());
+        let Some(cache) = self.cache.clone() else {
+            return self.download().await;
+        };
+        if let Some(file) = cache.get(&self.video_id, &self.stream.itag) {
+            return Ok(AudioStream {
+                input: Box::new(file),
+                hint: None,
+            });
+        }
+
+        let mut stream = self.download().await?;
+        if let Some(writer) = cache.writer(&self.video_id, &self.stream.itag) {
+            stream.input = Box::new(CachingSource::new(stream.input, writer));
+        }
+        Ok(stream)
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        Ok(self.metadata.clone())
+    }
+}
+
+/// Fetches only the metadata of a video, without deciphering a stream url.
+pub async fn video_metadata(
+    innertube: &Innertube,
+    url: &str,
+) -> Result<AuxMetadata, AudioStreamError> {
+    let video = innertube
+        .info(url)
+        .await
+        .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+    Ok(details_to_metadata(video.video_details))
+}
+
+/// Resolves YouTube videos and playlists, and searches YouTube for plain terms.
+pub struct YouTubeResolver {
+    innertube: Arc<Innertube>,
+    client: Client,
+    cache: Option<Arc<AudioCache>>,
+    sponsorblock: SponsorBlock,
+}
+
+impl YouTubeResolver {
+    pub fn new(
+        innertube: Arc<Innertube>,
+        client: Client,
+        cache: Option<Arc<AudioCache>>,
+        sponsorblock: SponsorBlock,
+    ) -> Self {
+        YouTubeResolver {
+            innertube,
+            client,
+            cache,
+            sponsorblock,
+        }
+    }
+}
+
+#[async_trait]
+impl SourceResolver for YouTubeResolver {
+    fn handles(&self, url: &str) -> bool {
+        is_youtube(url)
+    }
+
+    async fn entries(&self, url: &str) -> anyhow::Result<Vec<String>> {
+        match playlist_id(url) {
+            Some(list) => Ok(self.innertube.playlist(&list).await?),
+            None => Ok(vec![url.to_owned()]),
+        }
+    }
+
+    async fn resolve(&self, url: &str) -> anyhow::Result<Resolved> {
+        let mut input = YouTube::new(
+            self.innertube.clone(),
+            self.client.clone(),
+            self.cache.clone(),
+            url,
+        )
+        .await?;
+        // the track plays fine without skipping, so a failing api is no reason to fail it
+        let segments = match self.sponsorblock.segments(input.video_id()).await {
+            Ok(segments) => segments,
+            Err(why) => {
+                warn!("could not fetch segments of {url}: {why:?}");
+                Vec::new()
+            }
+        };
+        Ok(Resolved {
+            metadata: input.aux_metadata().await?,
+            live: false,
+            loudness: input.loudness(),
+            segments,
+            clip: Clip::from_url(url),
+            chapters: input.chapters().to_vec(),
+            input: input.into(),
+            artwork: None,
+        })
+    }
+
+    async fn metadata(&self, url: &str) -> anyhow::Result<AuxMetadata> {
+        Ok(video_metadata(&self.innertube, url).await?)
+    }
+
+    async fn search(&self, query: &str) -> Option<anyhow::Result<Vec<String>>> {
+        Some(self.innertube.search(query).await.map_err(Into::into))
+    }
+}
+
+fn is_youtube(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .is_some_and(|host| {
+            host == "youtube.com" || host.ends_with(".youtube.com") || host == "youtu.be"
+        })
+}
+
+/// How much of a file is downloaded to read its tags.
+const PROBE_BYTES: usize = 256 * 1024;
+/// How long to wait for a radio stream to send its current title.
+const ICY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How many redirects a linked file may go through.
+const MAX_REDIRECTS: usize = 10;
+
+/// An audio file or internet radio stream linked directly.
+///
+/// Like [`YouTube`], the link is requested when the source is created to check it really is audio
+/// and to read its tags. Streams without a content length, such as Icecast or Shoutcast radio,
+/// are treated as live and have no duration.
+pub struct HttpAudio {
+    client: Client,
+    url: String,
+    metadata: AuxMetadata,
+    content_length: Option<u64>,
+    /// Cover art embedded in the file's tags.
+    pub artwork: Option<Vec<u8>>,
+}
+
+impl HttpAudio {
+    pub async fn new(client: Client, url: &str) -> anyhow::Result<Self> {
+        let mut response = client.get(url).send().await?.error_for_status()?;
+
+        let headers = response.headers();
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        // web pages and the like are handled as if no resolver took the link
+        if !is_audio(content_type) {
+            return Err(anyhow::Error::new(UnsupportedLink(url.to_owned()))
+                .context(format!("Link is not audio but {content_type}")));
+        }
+
+        let content_length = response.content_length();
+        let station = headers
+            .get("icy-name")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let file_name = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| Some(url.path_segments()?.last()?.to_owned()))
+            .filter(|name| !name.is_empty());
+
+        let mut metadata = AuxMetadata {
+            title: station.clone().or(file_name.clone()),
+            channel: station,
+            source_url: Some(url.to_owned()),
+            ..AuxMetadata::default()
+        };
+
+        let mut artwork = None;
+        if content_length.is_some() {
+            let mut head = Vec::new();
+            while head.len() < PROBE_BYTES {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => head.extend_from_slice(&chunk),
+                    _ => break,
+                }
+            }
+
+            let extension = file_name
+                .as_deref()
+                .and_then(|name| name.rsplit_once('.'))
+                .map(|(_, extension)| extension);
+            if let Some(tags) = probe_tags(Box::new(Cursor::new(head)), extension) {
+                metadata.title = tags.title.or(metadata.title);
+                metadata.artist = tags.artist.clone();
+                metadata.channel = tags.artist;
+                metadata.album = tags.album;
+                metadata.duration = tags.duration;
+                artwork = tags.artwork;
+            }
+        }
+
+        Ok(HttpAudio {
+            client,
+            url: url.to_owned(),
+            metadata,
+            content_length,
+            artwork,
+        })
+    }
+
+    /// Whether this is a live stream, which plays until it is stopped and cannot seek.
+    pub fn live(&self) -> bool {
+        self.content_length.is_none()
+    }
+}
+
+impl From<HttpAudio> for Input {
+    fn from(val: HttpAudio) -> Self {
+        Input::Lazy(Box::new(val))
+    }
+}
+
+#[async_trait]
+impl Compose for HttpAudio {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
 
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
         let mut req = HttpRequest {
             client: self.client.clone(),
-            request: self.stream_url.clone(),
+            request: self.url.clone(),
             headers: HeaderMap::default(),
-            content_length,
+            content_length: self.content_length,
         };
 
         req.create_async().await
@@ -98,6 +445,295 @@ impl Compose for YouTube {
     }
 }
 
+/// A file from the local music library.
+pub struct LocalFile {
+    path: PathBuf,
+    metadata: AuxMetadata,
+}
+
+impl LocalFile {
+    pub fn new(path: PathBuf, metadata: AuxMetadata) -> Self {
+        LocalFile { path, metadata }
+    }
+}
+
+impl From<LocalFile> for Input {
+    fn from(val: LocalFile) -> Self {
+        Input::Lazy(Box::new(val))
+    }
+}
+
+#[async_trait]
+impl Compose for LocalFile {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let file = File::open(&self.path).map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+        let mut hint = Hint::new();
+        if let Some(extension) = self.path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        Ok(AudioStream {
+            input: Box::new(file),
+            hint: Some(hint),
+        })
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    fn should_create_async(&self) -> bool {
+        false
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        Ok(self.metadata.clone())
+    }
+}
+
+/// Resolves direct links to audio files and radio streams.
+///
+/// Any http link is accepted, so this should be registered after more specific resolvers. Links
+/// which turn out not to be audio fail with [`UnsupportedLink`]. Its client should come from
+/// [`public_client`], as the links come from anyone.
+pub struct HttpResolver {
+    client: Client,
+}
+
+impl HttpResolver {
+    pub fn new(client: Client) -> Self {
+        HttpResolver { client }
+    }
+}
+
+#[async_trait]
+impl SourceResolver for HttpResolver {
+    fn handles(&self, url: &str) -> bool {
+        url.starts_with("http://") || url.starts_with("https://")
+    }
+
+    async fn resolve(&self, url: &str) -> anyhow::Result<Resolved> {
+        if !is_public_url(&reqwest::Url::parse(url)?) {
+            anyhow::bail!("Links to private addresses are not allowed");
+        }
+
+        let mut input = HttpAudio::new(self.client.clone(), url).await?;
+        Ok(Resolved {
+            metadata: input.aux_metadata().await?,
+            live: input.live(),
+            artwork: input.artwork.take(),
+            loudness: None,
+            segments: Vec::new(),
+            clip: None,
+            chapters: Vec::new(),
+            input: input.into(),
+        })
+    }
+}
+
+/// Builds a client for links anyone can send, which only connects to public addresses.
+///
+/// Otherwise links could have the bot fetch from its own host or network. Names are checked as
+/// they are resolved for each connection, so redirects cannot get around it either.
+pub fn public_client() -> reqwest::Result<Client> {
+    let redirects = redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if !is_public_url(attempt.url()) {
+            attempt.error("redirected to a private address")
+        } else {
+            attempt.follow()
+        }
+    });
+
+    Client::builder()
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(redirects)
+        .build()
+}
+
+/// Resolves names to their public addresses only, see [`public_client`].
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether a link may be fetched, which for names is only known once they resolve.
+fn is_public_url(url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // ipv6 hosts keep their brackets
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_public(ip),
+        Err(_) => true,
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || a == 0
+                // shared address space of carrier-grade NAT
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                // besides unique local and link local addresses
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+fn is_audio(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.starts_with("audio/") || mime == "application/ogg" || mime == "application/octet-stream"
+}
+
+#[derive(Default)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+    pub artwork: Option<Vec<u8>>,
+}
+
+/// Reads the tags and duration of an audio file, which may also be just its start.
+pub fn probe_tags(source: Box<dyn MediaSource>, extension: Option<&str>) -> Option<Tags> {
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+
+    let source = MediaSourceStream::new(source, MediaSourceStreamOptions::default());
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+
+    let mut tags = Tags::default();
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        if let (Some(frames), Some(time_base)) = (params.n_frames, params.time_base) {
+            let time = time_base.calc_time(frames);
+            tags.duration =
+                Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac));
+        }
+    }
+
+    // tags may come before the container, like id3, or from inside it
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            read_tags(revision, &mut tags);
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        read_tags(revision, &mut tags);
+    }
+    Some(tags)
+}
+
+fn read_tags(revision: &MetadataRevision, tags: &mut Tags) {
+    for tag in revision.tags() {
+        let field = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => &mut tags.title,
+            Some(StandardTagKey::Artist) => &mut tags.artist,
+            Some(StandardTagKey::Album) => &mut tags.album,
+            _ => continue,
+        };
+        field.get_or_insert_with(|| tag.value.to_string());
+    }
+
+    if tags.artwork.is_none() {
+        tags.artwork = revision
+            .visuals()
+            .first()
+            .map(|visual| visual.data.to_vec());
+    }
+}
+
+/// Fetches the title a radio stream is currently playing, sent as ICY metadata.
+///
+/// This opens its own connection asking for metadata, so the audio stream stays clean.
+pub async fn icy_title(client: &Client, url: &str) -> Option<String> {
+    let read = async {
+        let mut response = client
+            .get(url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .await
+            .ok()?;
+        let interval: usize = response
+            .headers()
+            .get("icy-metaint")?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()?;
+
+        // metadata follows every `interval` bytes of audio, prefixed by its length in 16 bytes
+        let mut buffer = Vec::new();
+        while buffer.len() <= interval {
+            buffer.extend_from_slice(&response.chunk().await.ok()??);
+        }
+        let end = interval + 1 + usize::from(buffer[interval]) * 16;
+        while buffer.len() < end {
+            buffer.extend_from_slice(&response.chunk().await.ok()??);
+        }
+
+        let block = String::from_utf8_lossy(&buffer[interval + 1..end]).into_owned();
+        let title = block.split("StreamTitle='").nth(1)?.split("';").next()?;
+        Some(title.to_owned()).filter(|title| !title.is_empty())
+    };
+
+    tokio::time::timeout(ICY_TIMEOUT, read).await.ok().flatten()
+}
+
+/// Returns the playlist id of a YouTube url, which includes `watch` urls with a `list` parameter.
+pub fn playlist_id(url: &str) -> Option<String> {
+    if !is_youtube(url) {
+        return None;
+    }
+
+    reqwest::Url::parse(url)
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == "list")
+        .map(|(_, id)| id.into_owned())
+}
+
 fn details_to_metadata(details: VideoDetails) -> AuxMetadata {
     let length = details.length_seconds.parse::<u64>().unwrap();
     let thumbnail = details.thumbnails.thumbnails.first().unwrap().url.clone();
@@ -119,3 +755,32 @@ fn details_to_metadata(details: VideoDetails) -> AuxMetadata {
         ..AuxMetadata::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_youtube_hosts() {
+        assert!(is_youtube("https://youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(is_youtube("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(is_youtube("https://music.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(is_youtube("https://youtu.be/dQw4w9WgXcQ"));
+        assert!(!is_youtube("https://notyoutube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(!is_youtube("https://youtube.com.example.com/watch"));
+        assert!(!is_youtube("not a link"));
+    }
+
+    #[test]
+    fn private_links_are_refused() {
+        let public = |url| is_public_url(&reqwest::Url::parse(url).unwrap());
+        assert!(public("https://example.com/radio.mp3"));
+        assert!(public("http://93.184.215.14/song.ogg"));
+        assert!(!public("http://127.0.0.1:8080/admin"));
+        assert!(!public("http://10.0.0.2/song.mp3"));
+        assert!(!public("http://169.254.169.254/latest/meta-data"));
+        assert!(!public("http://[::1]/song.mp3"));
+        assert!(!public("http://[::ffff:192.168.1.1]/song.mp3"));
+        assert!(!public("http://[fd00::1]/song.mp3"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+/// Parses a timestamp as people write them, such as `90`, `1:30`, `01:02:03`, `90s` or `1h2m30s`.
+pub fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let timestamp = timestamp.trim();
+    if timestamp.contains(':') {
+        parse_clock(timestamp)
+    } else {
+        parse_units(timestamp)
+    }
+}
+
+/// Parses `m:ss` and `h:mm:ss` timestamps.
+pub fn parse_clock(timestamp: &str) -> Option<Duration> {
+    let parts: Vec<_> = timestamp.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+
+    let mut secs: u64 = 0;
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let value: u64 = part.parse().ok()?;
+        // minutes and seconds after the first part are always written with two digits
+        if i > 0 && (part.len() != 2 || value >= 60) {
+            return None;
+        }
+        secs = secs.checked_mul(60)?.checked_add(value)?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+/// Parses plain seconds and times with units as YouTube writes them in links, such as `1h2m30s`.
+fn parse_units(timestamp: &str) -> Option<Duration> {
+    if !timestamp.is_empty() && timestamp.chars().all(|c| c.is_ascii_digit()) {
+        return timestamp.parse().ok().map(Duration::from_secs);
+    }
+
+    let mut secs: u64 = 0;
+    let mut number = String::new();
+    // units go from hours down to seconds, each at most once
+    let mut units = [('h', 3600), ('m', 60), ('s', 1)].into_iter();
+    for c in timestamp.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let (_, unit) = units.find(|(name, _)| *name == c)?;
+        secs = secs.checked_add(number.parse::<u64>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    // a trailing number without a unit is ambiguous
+    (!timestamp.is_empty() && number.is_empty()).then_some(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(timestamp: &str) -> Option<u64> {
+        parse_timestamp(timestamp).map(|duration| duration.as_secs())
+    }
+
+    #[test]
+    fn parses_clock_timestamps() {
+        assert_eq!(secs("1:30"), Some(90));
+        assert_eq!(secs("01:02:03"), Some(3723));
+        assert_eq!(secs("90:00"), Some(5400));
+        assert_eq!(secs("1:5"), None);
+        assert_eq!(secs("1:60"), None);
+        assert_eq!(secs("1:02:03:04"), None);
+        assert_eq!(secs(":30"), None);
+        // too long to fit, rather than wrapping around to a short timestamp
+        assert_eq!(secs("999999999999999999:00"), None);
+        assert_eq!(secs("9999999999999999:00:00"), None);
+    }
+
+    #[test]
+    fn parses_timestamps_with_units() {
+        assert_eq!(secs("90"), Some(90));
+        assert_eq!(secs(" 90 "), Some(90));
+        assert_eq!(secs("90s"), Some(90));
+        assert_eq!(secs("1m30s"), Some(90));
+        assert_eq!(secs("1h2m30s"), Some(3750));
+        assert_eq!(secs("2m"), Some(120));
+        assert_eq!(secs("0s"), Some(0));
+        assert_eq!(secs(""), None);
+        assert_eq!(secs("s"), None);
+        assert_eq!(secs("30s1m"), None);
+        assert_eq!(secs("1m1m"), None);
+        assert_eq!(secs("1m30"), None);
+        assert_eq!(secs("-5"), None);
+        assert_eq!(secs("99999999999999999999"), None);
+        assert_eq!(secs("9999999999999999h"), None);
+        assert_eq!(secs("5124095576030431h1m"), None);
+    }
+}
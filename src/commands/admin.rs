@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use poise::{builtins::register_application_commands, serenity_prelude as serenity};
 
-use crate::{traits::ContextExt, Context, Data, Error};
+use crate::{audio::session::FadeCurve, traits::ContextExt, Context, Data, Error};
 
 // TODO: move to a more permanent solution using button listener
 /// Create limited-time buttons where users can choose roles for themselves
@@ -91,6 +93,277 @@ pub async fn self_role(
     Ok(())
 }
 
+/// Set the volume every music session in this server starts with
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn default_volume(
+    ctx: Context<'_>,
+    #[description = "volume in percent"]
+    #[min = 0]
+    #[max = 200]
+    volume: u16,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data()
+        .store
+        .blocking(move |store| {
+            let mut settings = store.guild_settings(guild_id)?;
+            settings.volume = volume;
+            store.save_guild_settings(guild_id, &settings)
+        })
+        .await?;
+
+    ctx.say(format!("Default volume set to {volume}%")).await?;
+    Ok(())
+}
+
+/// Change when the bot leaves the voice channel on its own
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn auto_leave(
+    ctx: Context<'_>,
+    #[description = "seconds to stay after everyone left"] alone: Option<u64>,
+    #[description = "seconds to stay with an empty queue"] idle: Option<u64>,
+    #[description = "stay in the voice channel 24/7"] always_on: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let settings = ctx
+        .data()
+        .store
+        .blocking(move |store| {
+            let mut settings = store.guild_settings(guild_id)?;
+            if let Some(alone) = alone {
+                settings.alone_timeout = alone;
+            }
+            if let Some(idle) = idle {
+                settings.idle_timeout = idle;
+            }
+            if let Some(always_on) = always_on {
+                settings.always_on = always_on;
+            }
+            store.save_guild_settings(guild_id, &settings)?;
+            Ok(settings)
+        })
+        .await?;
+
+    let reply = if settings.always_on {
+        "Staying in the voice channel 24/7".to_owned()
+    } else {
+        format!(
+            "Leaving {}s after everyone left and after {}s with an empty queue",
+            settings.alone_timeout, settings.idle_timeout
+        )
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Set who can control music playback and how skips are voted on
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn dj(
+    ctx: Context<'_>,
+    #[description = "role allowed to control playback"] role: Option<serenity::Role>,
+    #[description = "let everyone control playback again"] remove_role: Option<bool>,
+    #[description = "count skips from non-DJs as votes"] vote_skip: Option<bool>,
+    #[description = "percent of listeners needed to skip"]
+    #[min = 1]
+    #[max = 100]
+    threshold: Option<u8>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let role = role.map(|role| role.id.get());
+    let settings = ctx
+        .data()
+        .store
+        .blocking(move |store| {
+            let mut settings = store.guild_settings(guild_id)?;
+            if let Some(role) = role {
+                settings.dj_role = Some(role);
+            }
+            if remove_role == Some(true) {
+                settings.dj_role = None;
+            }
+            if let Some(vote_skip) = vote_skip {
+                settings.vote_skip = vote_skip;
+            }
+            if let Some(threshold) = threshold {
+                settings.vote_threshold = threshold;
+            }
+            store.save_guild_settings(guild_id, &settings)?;
+            Ok(settings)
+        })
+        .await?;
+
+    let role = match settings.dj_role {
+        Some(role) => format!("<@&{role}>"),
+        // with votes but no role, only those who can manage the server skip directly
+        None if settings.vote_skip => "server managers".to_owned(),
+        None => "everyone".to_owned(),
+    };
+    let votes = if settings.vote_skip {
+        format!("{}% of listeners", settings.vote_threshold)
+    } else {
+        "off".to_owned()
+    };
+    ctx.say(format!("DJ: {role}\nVote skip: {votes}")).await?;
+    Ok(())
+}
+
+/// Even out the loudness of tracks so they all play at a similar level
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn normalize(
+    ctx: Context<'_>,
+    #[description = "normalize the loudness of tracks"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+
+    data.store
+        .blocking(move |store| {
+            let mut settings = store.guild_settings(guild_id.get())?;
+            settings.normalize = enabled;
+            store.save_guild_settings(guild_id.get(), &settings)
+        })
+        .await?;
+
+    // the current session picks it up right away, even in the middle of a track
+    if data.songbird.get(guild_id).is_some() {
+        data.sessions
+            .with(guild_id, |session| session.filters.set_normalize(enabled));
+    }
+
+    let reply = if enabled {
+        "Loudness normalization turned on"
+    } else {
+        "Loudness normalization turned off"
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Fade tracks into each other instead of cutting between them
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Admin",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn crossfade(
+    ctx: Context<'_>,
+    #[description = "seconds tracks overlap, 0 to turn crossfading off"]
+    #[min = 0]
+    #[max = 12]
+    seconds: u64,
+    #[description = "how the volumes change during the fade"] curve: Option<FadeCurve>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+
+    let settings = data
+        .store
+        .blocking(move |store| {
+            let mut settings = store.guild_settings(guild_id.get())?;
+            settings.crossfade = seconds;
+            if let Some(curve) = curve {
+                settings.fade_curve = curve;
+            }
+            store.save_guild_settings(guild_id.get(), &settings)?;
+            Ok(settings)
+        })
+        .await?;
+
+    if data.songbird.get(guild_id).is_some() {
+        data.sessions.with(guild_id, |session| {
+            session.crossfade = Duration::from_secs(seconds);
+            session.fade_curve = settings.fade_curve;
+        });
+    }
+
+    let reply = if seconds == 0 {
+        "Crossfading turned off".to_owned()
+    } else {
+        format!(
+            "Crossfading over {seconds}s with the {} curve",
+            settings.fade_curve.name()
+        )
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Show or clear the audio cache
+#[poise::command(
+    slash_command,
+    owners_only,
+    category = "Admin",
+    subcommands("cache_stats", "cache_purge"),
+    subcommand_required
+)]
+pub async fn cache(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show how much of the audio cache is used and how often it is hit
+#[poise::command(slash_command, owners_only, category = "Admin", rename = "stats")]
+async fn cache_stats(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(cache) = ctx.data().cache.clone() else {
+        ctx.say_ephemeral("No audio cache is set up").await?;
+        return Ok(());
+    };
+
+    let stats = cache.stats();
+    let plays = stats.hits + stats.misses;
+    let hit_rate = if plays == 0 {
+        0.0
+    } else {
+        stats.hits as f64 / plays as f64 * 100.0
+    };
+    ctx.say_ephemeral(format!(
+        "{} tracks cached, {} of {} MiB used\n{} of {plays} plays since startup came from the cache ({hit_rate:.0}%)",
+        stats.entries,
+        stats.size / 1024 / 1024,
+        stats.limit / 1024 / 1024,
+        stats.hits,
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Remove every track from the audio cache
+#[poise::command(slash_command, owners_only, category = "Admin", rename = "purge")]
+async fn cache_purge(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(cache) = ctx.data().cache.clone() else {
+        ctx.say_ephemeral("No audio cache is set up").await?;
+        return Ok(());
+    };
+
+    let freed = tokio::task::spawn_blocking(move || cache.purge()).await?;
+    ctx.say_ephemeral(format!(
+        "Purged the audio cache, freeing {} MiB",
+        freed / 1024 / 1024
+    ))
+    .await?;
+    Ok(())
+}
+
 #[poise::command(slash_command, guild_only, owners_only, category = "Admin")]
 pub async fn sync(ctx: Context<'_>) -> Result<(), Error> {
     register_application_commands(ctx, false).await?;
@@ -103,6 +376,16 @@ pub async fn sync_global(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn commands() -> [poise::Command<Data, Error>; 3] {
-    [self_role(), sync(), sync_global()]
+pub fn commands() -> [poise::Command<Data, Error>; 9] {
+    [
+        self_role(),
+        default_volume(),
+        auto_leave(),
+        dj(),
+        normalize(),
+        crossfade(),
+        cache(),
+        sync(),
+        sync_global(),
+    ]
 }
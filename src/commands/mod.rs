@@ -1,7 +1,7 @@
 use crate::{Data, Error};
 
 mod admin;
-mod music;
+pub mod music;
 mod others;
 
 pub fn commands() -> Vec<poise::Command<Data, Error>> {
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use poise::serenity_prelude as serenity;
+
+use super::{choice_label, duration_hhmmss, guild_queue, require_dj, seek_current, TrackData};
+use crate::{audio::chapters::current_chapter, paginate::paginate, traits::ContextExt, Context};
+
+/// List the chapters of the current track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn chapters(ctx: Context<'_>) -> Result<()> {
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+    let Some(track) = queue.current() else {
+        ctx.say_ephemeral("Nothing is playing right now").await?;
+        return Ok(());
+    };
+
+    let data = track.data::<TrackData>();
+    if data.chapters.is_empty() {
+        ctx.say_ephemeral("The current track has no chapters")
+            .await?;
+        return Ok(());
+    }
+
+    let current = current_chapter(&data.chapters, data.position()).map(|(index, _)| index);
+    let pages: Vec<String> = data
+        .chapters
+        .chunks(10)
+        .enumerate()
+        .map(|(page, chapters)| {
+            chapters
+                .iter()
+                .enumerate()
+                .map(|(i, chapter)| {
+                    let index = page * 10 + i;
+                    let line = format!(
+                        "{}. `{}` {}",
+                        index + 1,
+                        duration_hhmmss(&chapter.start),
+                        chapter.title
+                    );
+                    if Some(index) == current {
+                        format!("**{line}**")
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+
+    let title = data
+        .metadata
+        .title
+        .as_deref()
+        .unwrap_or("the current track");
+    paginate(ctx, &format!("Chapters of {title}"), &pages).await?;
+    Ok(())
+}
+
+/// Jump to a chapter of the current track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn chapter(
+    ctx: Context<'_>,
+    #[description = "chapter number, next or prev"]
+    #[autocomplete = "autocomplete_chapter"]
+    chapter: String,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+    let Some(track) = queue.current() else {
+        ctx.say_ephemeral("Nothing is playing right now").await?;
+        return Ok(());
+    };
+
+    let data = track.data::<TrackData>();
+    let chapters = &data.chapters;
+    if chapters.is_empty() {
+        ctx.say_ephemeral("The current track has no chapters")
+            .await?;
+        return Ok(());
+    }
+
+    let position = data.position();
+    let current = current_chapter(chapters, position).map_or(0, |(index, _)| index);
+    let index = match chapter.trim().to_lowercase().as_str() {
+        "next" => current + 1,
+        // like a previous button, this goes back to the start of the current chapter first
+        "prev" | "previous"
+            if position.saturating_sub(chapters[current].start) > Duration::from_secs(3) =>
+        {
+            current
+        }
+        "prev" | "previous" => current.saturating_sub(1),
+        number => match number.parse::<usize>() {
+            Ok(number) if number > 0 => number - 1,
+            _ => {
+                ctx.say_ephemeral("Use a chapter number, next or prev")
+                    .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let Some(target) = chapters.get(index) else {
+        ctx.say_ephemeral(format!(
+            "There is no chapter {}, the track has {}",
+            index + 1,
+            chapters.len()
+        ))
+        .await?;
+        return Ok(());
+    };
+    let start = target.start;
+    seek_current(ctx, |_| start).await
+}
+
+async fn autocomplete_chapter<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> serenity::CreateAutocompleteResponse<'a> {
+    let response = serenity::CreateAutocompleteResponse::new();
+    let Some(handler_lock) = ctx.data().songbird.get(ctx.guild_id().unwrap()) else {
+        return response;
+    };
+    let Some(track) = handler_lock.lock().await.queue().current() else {
+        return response;
+    };
+
+    let data = track.data::<TrackData>();
+    let partial = partial.to_lowercase();
+    let choices = data
+        .chapters
+        .iter()
+        .enumerate()
+        .filter(|(_, chapter)| chapter.title.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|(index, chapter)| {
+            let label = format!("{}. {}", index + 1, chapter.title);
+            serenity::AutocompleteChoice::new(choice_label(label), (index + 1).to_string())
+        })
+        .collect::<Vec<_>>();
+    response.set_choices(choices)
+}
@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use poise::serenity_prelude as serenity;
+use songbird::{
+    tracks::{PlayMode, ReadyState, TrackHandle, TrackQueue},
+    Event, EventContext, EventHandler,
+};
+use tracing::warn;
+
+use super::TrackData;
+use crate::{
+    audio::{segments::time_left, session::RepeatMode},
+    Data,
+};
+
+/// How often the volumes are adjusted while fading.
+pub(super) const FADE_TICK: Duration = Duration::from_millis(100);
+/// How long before the end of a track the next one is opened, so it starts without a gap.
+const PREWARM: Duration = Duration::from_secs(10);
+
+/// Opens the next track ahead of time and fades between tracks as the current one ends.
+///
+/// The queue only plays its first track, so the next one is started early here and simply keeps
+/// playing once the queue moves on to it.
+pub(super) struct Crossfader {
+    pub guild_id: serenity::GuildId,
+    pub queue: TrackQueue,
+    pub data: Arc<Data>,
+}
+
+impl Crossfader {
+    async fn tick(&self) -> Result<()> {
+        let (fade, curve, volume, repeat, speed) =
+            self.data.sessions.with(self.guild_id, |session| {
+                (
+                    session.crossfade,
+                    session.fade_curve,
+                    session.volume,
+                    session.repeat,
+                    session.filters.get().speed,
+                )
+            });
+
+        let tracks = self.queue.current_queue();
+        let Some(current) = tracks.first() else {
+            return Ok(());
+        };
+        let next = tracks.get(1);
+        let state = current.get_info().await?;
+
+        if state.playing != PlayMode::Play {
+            // a track which was already fading in has to pause along with the current one
+            if let Some(next) = next {
+                if state.playing == PlayMode::Pause
+                    && next.get_info().await?.playing == PlayMode::Play
+                {
+                    next.pause()?;
+                }
+            }
+            return Ok(());
+        }
+
+        // the time left in the source plays faster or slower with filters, and a skipped outro
+        // ends the track early
+        let speed = speed.max(0.1);
+        let track_data = current.data::<TrackData>();
+        let remaining = track_data
+            .end()
+            .map(|end| time_left(end, track_data.position(), &track_data.segments).div_f32(speed));
+
+        let (Some(next), Some(remaining)) = (next, remaining) else {
+            return restore_volume(current, state.volume, volume);
+        };
+
+        if remaining <= PREWARM.max(fade) {
+            let next_state = next.get_info().await?;
+            if matches!(next_state.ready, ReadyState::Uninitialised) {
+                drop(next.make_playable());
+            }
+
+            // a repeated track is put in front of the next one once it ends, so it cannot fade
+            if !fade.is_zero() && remaining <= fade && repeat != RepeatMode::Track {
+                let progress = 1.0 - remaining.as_secs_f32() / fade.as_secs_f32();
+                let (outgoing, incoming) = curve.gains(progress);
+                current.set_volume(volume * outgoing)?;
+                next.set_volume(volume * incoming)?;
+                if next_state.playing != PlayMode::Play {
+                    next.play()?;
+                }
+                return Ok(());
+            }
+        }
+        restore_volume(current, state.volume, volume)
+    }
+}
+
+/// Brings a track back to the session volume, after it took over from a fade which did not
+/// finish or was skipped halfway.
+fn restore_volume(track: &TrackHandle, track_volume: f32, volume: f32) -> Result<()> {
+    if (track_volume - volume).abs() > f32::EPSILON {
+        track.set_volume(volume)?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl EventHandler for Crossfader {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if let Err(why) = self.tick().await {
+            warn!("could not crossfade in guild {}: {why:?}", self.guild_id);
+        }
+        None
+    }
+}
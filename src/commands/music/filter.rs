@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+use super::require_dj;
+use crate::{
+    audio::filters::{FilterPreset, FilterSettings},
+    traits::ContextExt,
+    Context,
+};
+
+/// Change how the music sounds
+#[poise::command(
+    slash_command,
+    category = "Music",
+    guild_only,
+    subcommands("preset", "custom"),
+    subcommand_required
+)]
+pub async fn filter(_ctx: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// Apply a filter preset, or turn filters off
+#[poise::command(slash_command, category = "Music", guild_only)]
+async fn preset(
+    ctx: Context<'_>,
+    #[description = "filter preset"] preset: FilterPreset,
+) -> Result<()> {
+    if !apply(ctx, preset.into()).await? {
+        return Ok(());
+    }
+
+    let reply = match preset {
+        FilterPreset::Off => "Turned filters off".to_owned(),
+        preset => format!("Applied the {} filter", preset.name()),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Tune the filters yourself
+#[poise::command(slash_command, category = "Music", guild_only)]
+async fn custom(
+    ctx: Context<'_>,
+    #[description = "bass gain in dB"]
+    #[min = -12]
+    #[max = 12]
+    bass: Option<f32>,
+    #[description = "treble gain in dB"]
+    #[min = -12]
+    #[max = 12]
+    treble: Option<f32>,
+    #[description = "playback speed, also changes the pitch"]
+    #[min = 0.5]
+    #[max = 2]
+    speed: Option<f32>,
+    #[description = "rotations between the left and right ear per second"]
+    #[min = 0]
+    #[max = 2]
+    rotation: Option<f32>,
+    #[description = "remove centered vocals"] karaoke: Option<bool>,
+) -> Result<()> {
+    let off = FilterSettings::default();
+    let settings = FilterSettings {
+        bass: bass.unwrap_or(off.bass),
+        treble: treble.unwrap_or(off.treble),
+        speed: speed.unwrap_or(off.speed),
+        rotation: rotation.unwrap_or(off.rotation),
+        karaoke: karaoke.unwrap_or(off.karaoke),
+    };
+    if !apply(ctx, settings).await? {
+        return Ok(());
+    }
+
+    ctx.say(format!(
+        "Filters set to bass {:+} dB, treble {:+} dB, speed {}x, rotation {} Hz, karaoke {}",
+        settings.bass,
+        settings.treble,
+        settings.speed,
+        settings.rotation,
+        if settings.karaoke { "on" } else { "off" }
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Sets the filters of the guild, which the current track picks up right away.
+///
+/// Returns `false` after replying to the author if they are not a DJ or the bot is not in a voice
+/// channel.
+async fn apply(ctx: Context<'_>, settings: FilterSettings) -> Result<bool> {
+    if !require_dj(ctx).await? {
+        return Ok(false);
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    if ctx.data().songbird.get(guild_id).is_none() {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(false);
+    }
+
+    let filters = ctx
+        .data()
+        .sessions
+        .with(guild_id, |session| session.filters.clone());
+    filters.set(settings);
+    Ok(true)
+}
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use poise::serenity_prelude as serenity;
+
+use super::{choice_label, duration_hhmmss, enqueue_playlist, enqueue_source, join_author_channel};
+use crate::{
+    audio::library::{Library, LibraryTrack},
+    paginate::paginate,
+    traits::ContextExt,
+    Context,
+};
+
+/// Play music from the server's own library
+#[poise::command(
+    slash_command,
+    category = "Music",
+    guild_only,
+    subcommands("search", "play", "album"),
+    subcommand_required
+)]
+pub async fn library(_ctx: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// Search the library for tracks
+#[poise::command(slash_command, category = "Music", guild_only)]
+async fn search(
+    ctx: Context<'_>,
+    #[description = "title, artist or album"] query: String,
+) -> Result<()> {
+    let Some(library) = guild_library(ctx).await? else {
+        return Ok(());
+    };
+
+    let tracks = library.search(&query);
+    if tracks.is_empty() {
+        ctx.say_ephemeral(format!("No tracks in the library match {query}."))
+            .await?;
+        return Ok(());
+    }
+
+    let pages: Vec<String> = tracks
+        .chunks(10)
+        .enumerate()
+        .map(|(page, tracks)| {
+            tracks
+                .iter()
+                .enumerate()
+                .map(|(i, track)| format!("{}. {}", page * 10 + i + 1, track_line(track)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+
+    paginate(ctx, &format!("Library results for {query}"), &pages).await?;
+    Ok(())
+}
+
+/// Play the best matching track from the library
+#[poise::command(slash_command, category = "Music", guild_only)]
+async fn play(
+    ctx: Context<'_>,
+    #[description = "title, artist or album"]
+    #[autocomplete = "autocomplete_track"]
+    query: String,
+) -> Result<()> {
+    let Some(library) = guild_library(ctx).await? else {
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    // autocompleted choices are already links to a track
+    let track = library
+        .get(&query)
+        .or_else(|| library.search(&query).into_iter().next());
+    let Some(track) = track else {
+        ctx.say_ephemeral(format!("No tracks in the library match {query}."))
+            .await?;
+        return Ok(());
+    };
+
+    let Some(handler_lock) = join_author_channel(ctx).await? else {
+        return Ok(());
+    };
+    let resolved = ctx.data().resolvers.resolve(&track.url()).await?;
+    enqueue_source(ctx, &handler_lock, resolved).await
+}
+
+/// Play a whole album from the library
+#[poise::command(slash_command, category = "Music", guild_only)]
+async fn album(
+    ctx: Context<'_>,
+    #[description = "album name"]
+    #[autocomplete = "autocomplete_album"]
+    name: String,
+) -> Result<()> {
+    let Some(library) = guild_library(ctx).await? else {
+        return Ok(());
+    };
+    ctx.defer().await?;
+
+    let urls: Vec<_> = library.album(&name).iter().map(LibraryTrack::url).collect();
+    let Some(resolver) = urls.first().and_then(|url| ctx.data().resolvers.find(url)) else {
+        ctx.say_ephemeral(format!("There is no album called {name} in the library."))
+            .await?;
+        return Ok(());
+    };
+
+    let Some(handler_lock) = join_author_channel(ctx).await? else {
+        return Ok(());
+    };
+    enqueue_playlist(ctx, &handler_lock, resolver, urls).await
+}
+
+/// Returns the library, replying to the author if none is set up.
+async fn guild_library(ctx: Context<'_>) -> Result<Option<Arc<Library>>> {
+    let library = ctx.data().library.clone();
+    if library.is_none() {
+        ctx.say_ephemeral("No music library is set up").await?;
+    }
+    Ok(library)
+}
+
+fn track_line(track: &LibraryTrack) -> String {
+    let mut line = track.title.clone();
+    if let Some(artist) = &track.artist {
+        line += &format!(" - {artist}");
+    }
+    if let Some(album) = &track.album {
+        line += &format!(" ({album})");
+    }
+    if let Some(duration) = &track.duration {
+        line += &format!(" [{}]", duration_hhmmss(duration));
+    }
+    line
+}
+
+async fn autocomplete_track<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> serenity::CreateAutocompleteResponse<'a> {
+    let response = serenity::CreateAutocompleteResponse::new();
+    let Some(library) = ctx.data().library.clone() else {
+        return response;
+    };
+
+    let choices = library
+        .search(partial)
+        .iter()
+        // choice values have the same length limit as names
+        .filter(|track| track.url().len() <= 100)
+        .take(25)
+        .map(|track| {
+            serenity::AutocompleteChoice::new(choice_label(track_line(track)), track.url())
+        })
+        .collect::<Vec<_>>();
+    response.set_choices(choices)
+}
+
+async fn autocomplete_album<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> serenity::CreateAutocompleteResponse<'a> {
+    let response = serenity::CreateAutocompleteResponse::new();
+    let Some(library) = ctx.data().library.clone() else {
+        return response;
+    };
+
+    let choices = library
+        .albums(partial)
+        .into_iter()
+        .take(25)
+        .map(|album| serenity::AutocompleteChoice::new(choice_label(album.clone()), album))
+        .collect::<Vec<_>>();
+    response.set_choices(choices)
+}
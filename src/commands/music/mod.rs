@@ -0,0 +1,2706 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use poise::{serenity_prelude as serenity, CreateReply};
+use rand::{seq::SliceRandom, Rng};
+use tokio::sync::Mutex;
+
+use songbird::{
+    input::{AuxMetadata, Input},
+    tracks::{PlayMode, Track, TrackHandle, TrackQueue, TrackState},
+    Call, Event, EventContext, EventHandler, TrackEvent,
+};
+use tracing::warn;
+
+use crate::{
+    audio::{
+        chapters::{current_chapter, Chapter},
+        clip::Clip,
+        filters::{Filtered, Filters, SourceClock},
+        history::HistoryEntry,
+        resolver::{Resolved, SourceResolver, UnsupportedLink},
+        segments::{outro_start, time_left, Segment},
+        session::{RepeatMode, Session},
+        sources::icy_title,
+        timestamp::parse_timestamp,
+    },
+    paginate::paginate,
+    store::{GuildSettings, SavedPlayer, SavedTrack, Store},
+    traits::ContextExt,
+    Command, Context, Data,
+};
+
+mod chapters;
+mod crossfade;
+mod filter;
+mod library;
+mod player;
+mod segments;
+
+use crossfade::{Crossfader, FADE_TICK};
+pub use player::handle_player_button;
+use player::{PlayerUpdater, PLAYER_REFRESH};
+use segments::{SegmentSkipper, SEGMENT_CHECK};
+
+/// How far `/forward` and `/rewind` move when no step is given.
+const SEEK_STEP: Duration = Duration::from_secs(10);
+/// How many tracks may fail in a row before playback stops.
+const MAX_FAILURES: u8 = 3;
+/// How many search results are offered when picking a track.
+const PICK_RESULTS: usize = 5;
+/// How long the requester has to pick a search result.
+const PICK_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct TrackData {
+    metadata: AuxMetadata,
+    /// See [`Resolved::live`].
+    live: bool,
+    /// Who asked for the track, `None` if nobody did or the track was saved without its requester.
+    requester: Option<serenity::UserId>,
+    /// Whether the track was picked by autoplay rather than requested by someone.
+    autoplayed: bool,
+    /// Loudness the source reported, see [`Resolved::loudness`].
+    loudness: Option<f32>,
+    /// Parts of the track which are skipped, see [`Resolved::segments`].
+    segments: Vec<Segment>,
+    /// Range of the track which is played, from the link or given with `/play`. Only set when it
+    /// passed [`check_clip`].
+    clip: Option<Clip>,
+    chapters: Vec<Chapter>,
+    clock: Arc<SourceClock>,
+}
+
+impl TrackData {
+    /// Splits a resolved source into its input and the data of the track which plays it.
+    fn new(resolved: Resolved, requester: Option<serenity::UserId>) -> (Input, Self) {
+        let clip = resolved.clip.filter(|clip| {
+            let checked = check_clip(clip, &resolved.metadata, resolved.live);
+            if let Err(why) = &checked {
+                let url = resolved.metadata.source_url.as_deref().unwrap_or_default();
+                warn!("ignoring clip of {url}: {why}");
+            }
+            checked.is_ok()
+        });
+        let data = TrackData {
+            metadata: resolved.metadata,
+            live: resolved.live,
+            requester,
+            autoplayed: false,
+            loudness: resolved.loudness,
+            segments: resolved.segments,
+            clip,
+            chapters: resolved.chapters,
+            clock: Arc::default(),
+        };
+        (resolved.input, data)
+    }
+
+    /// Who the track shows as requested by.
+    fn requester_label(&self) -> String {
+        match self.requester {
+            _ if self.autoplayed => "Autoplay".to_owned(),
+            Some(user_id) => format!("<@{user_id}>"),
+            None => "Unknown".to_owned(),
+        }
+    }
+
+    /// How far the track has played, in time of the source like its duration.
+    fn position(&self) -> Duration {
+        self.clock.get()
+    }
+
+    /// Where the track stops playing, `None` when its length is unknown, as for live streams.
+    ///
+    /// Besides the end of a clip, that is the start of a skipped outro, which has nothing after it
+    /// to skip to.
+    fn end(&self) -> Option<Duration> {
+        let duration = self.metadata.duration?;
+        let clip_end = self.clip.and_then(|clip| clip.end);
+        let outro = outro_start(&self.segments, duration);
+        Some(
+            clip_end
+                .into_iter()
+                .chain(outro)
+                .fold(duration, Duration::min),
+        )
+    }
+}
+
+/// Periodically saves the queue of a guild so it can be restored after a restart.
+struct QueueSaver {
+    guild_id: serenity::GuildId,
+    voice_channel: serenity::ChannelId,
+    text_channel: serenity::ChannelId,
+    queue: TrackQueue,
+    data: Arc<Data>,
+}
+
+impl QueueSaver {
+    async fn save(&self) -> Result<()> {
+        let tracks = self.queue.current_queue();
+        let position = tracks
+            .first()
+            .map_or(Duration::ZERO, |track| track.data::<TrackData>().position());
+
+        let tracks = tracks
+            .iter()
+            .filter_map(|track| {
+                let data = track.data::<TrackData>();
+                Some(SavedTrack {
+                    url: data.metadata.source_url.clone()?,
+                    requester: data.requester.map(serenity::UserId::get),
+                    autoplayed: data.autoplayed,
+                    clip: data.clip,
+                })
+            })
+            .collect();
+
+        let repeat = self
+            .data
+            .sessions
+            .with(self.guild_id, |session| session.repeat);
+        let player = SavedPlayer {
+            guild_id: self.guild_id.get(),
+            voice_channel: self.voice_channel.get(),
+            text_channel: self.text_channel.get(),
+            position,
+            repeat,
+            tracks,
+        };
+        self.data
+            .store
+            .blocking(move |store| store.save_player(&player))
+            .await
+    }
+}
+
+#[async_trait]
+impl EventHandler for QueueSaver {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if let Err(why) = self.save().await {
+            warn!("could not save queue for guild {}: {why:?}", self.guild_id);
+        }
+        None
+    }
+}
+
+/// Puts finished tracks back into the queue according to the guild's repeat mode.
+///
+/// Songbird inputs cannot be replayed once consumed, so every repeat resolves a fresh source.
+/// Only tracks which ended naturally are repeated, skipped or removed tracks are left out.
+/// A single repeating track never ends, as [`TrackLooper`] has songbird loop it instead.
+struct RepeatHandler {
+    guild_id: serenity::GuildId,
+    queue: TrackQueue,
+    data: Arc<Data>,
+}
+
+impl RepeatHandler {
+    async fn repeat(&self, ended: &TrackHandle, mode: RepeatMode) -> Result<()> {
+        let track_data = ended.data::<TrackData>();
+        let url = track_data
+            .metadata
+            .source_url
+            .as_deref()
+            .unwrap_or_default();
+        let resolved = self.data.resolvers.resolve(url).await?;
+
+        let Some(handler_lock) = self.data.songbird.get(self.guild_id) else {
+            return Ok(());
+        };
+        let mut handler = handler_lock.lock().await;
+        let track = guild_track(&self.data, self.guild_id, resolved.input, track_data);
+        handler.enqueue(track).await;
+        drop(handler);
+
+        if mode == RepeatMode::ShuffleRepeat {
+            self.queue.modify_queue(|tracks| {
+                let len = tracks.len();
+                if len > 2 {
+                    let track = tracks.pop_back().unwrap();
+                    tracks.insert(rand::thread_rng().gen_range(1..len), track);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for RepeatHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        let mode = self
+            .data
+            .sessions
+            .with(self.guild_id, |session| session.repeat);
+        if matches!(mode, RepeatMode::Off | RepeatMode::Track) {
+            return None;
+        }
+
+        for (state, track) in *tracks {
+            if !matches!(state.playing, PlayMode::End) {
+                continue;
+            }
+            if let Err(why) = self.repeat(track, mode).await {
+                warn!("could not repeat track: {why:?}");
+            }
+        }
+        None
+    }
+}
+
+/// Has songbird loop every track which starts playing while a single track repeats.
+struct TrackLooper {
+    guild_id: serenity::GuildId,
+    data: Arc<Data>,
+}
+
+#[async_trait]
+impl EventHandler for TrackLooper {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        let mode = self
+            .data
+            .sessions
+            .with(self.guild_id, |session| session.repeat);
+        for (_, track) in *tracks {
+            set_looping(track, mode);
+        }
+        None
+    }
+}
+
+/// Loops a track when the repeat mode repeats a single track, and stops looping it otherwise.
+///
+/// Songbird loops by seeking back to the start, so the track plays again without resolving it.
+fn set_looping(track: &TrackHandle, mode: RepeatMode) {
+    let result = if mode == RepeatMode::Track {
+        track.enable_loop()
+    } else {
+        track.disable_loop()
+    };
+    if let Err(why) = result {
+        warn!("could not change looping of track: {why:?}");
+    }
+}
+
+/// Adds tracks which were actually played to the guild's history once they end.
+struct HistoryRecorder {
+    guild_id: serenity::GuildId,
+    data: Arc<Data>,
+}
+
+#[async_trait]
+impl EventHandler for HistoryRecorder {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        for (state, track) in *tracks {
+            if state.play_time.is_zero() {
+                continue;
+            }
+            let unrecorded = self.data.sessions.with(self.guild_id, |session| {
+                session.unrecorded.take_if(|uuid| *uuid == track.uuid())
+            });
+            if unrecorded.is_some() {
+                continue;
+            }
+
+            let track_data = track.data::<TrackData>();
+            let Some(url) = track_data.metadata.source_url.clone() else {
+                continue;
+            };
+            let entry = HistoryEntry {
+                url,
+                title: track_data
+                    .metadata
+                    .title
+                    .clone()
+                    .unwrap_or("No Title".to_owned()),
+                requester: track_data.requester_label(),
+                clip: track_data.clip,
+                played_at: SystemTime::now(),
+            };
+            if let Err(why) = self.data.history.record(self.guild_id, entry).await {
+                warn!("could not record history: {why:?}");
+            }
+        }
+        None
+    }
+}
+
+/// Reports tracks which fail while playing and retries each of them once from where it stopped.
+///
+/// The queue moves on by itself after a failure, so giving up on a track just leaves it skipped.
+/// Playback stops entirely once too many tracks fail in a row, where a track and its retry count
+/// once. Any track which ends after playing breaks the streak.
+///
+/// Upcoming tracks are opened ahead of time and can fail before they play. The queue keeps those
+/// in place, so they are swapped for a fresh copy there and do not count towards the streak.
+struct FailureHandler {
+    guild_id: serenity::GuildId,
+    queue: TrackQueue,
+    http: Arc<serenity::Http>,
+    data: Arc<Data>,
+}
+
+impl FailureHandler {
+    async fn failed(&self, track: &TrackHandle, state: &TrackState) -> Result<()> {
+        let PlayMode::Errored(why) = &state.playing else {
+            return Ok(());
+        };
+
+        let track_data = track.data::<TrackData>();
+        let title = track_data.metadata.title.as_deref().unwrap_or("No Title");
+        let url = track_data
+            .metadata
+            .source_url
+            .as_deref()
+            .unwrap_or_default();
+        warn!(
+            "track {title} ({url}) failed in guild {} at {:?}: {why:?}",
+            self.guild_id,
+            track_data.position()
+        );
+
+        let upcoming = self
+            .queue
+            .current_queue()
+            .iter()
+            .any(|queued| queued.uuid() == track.uuid());
+        let (failures, retried, channel) = self.data.sessions.with(self.guild_id, |session| {
+            let retried = session.retried == Some(track.uuid());
+            if !retried && !upcoming {
+                session.failures += 1;
+            }
+            let failures = session.failures;
+            // stopping ends the streak, so the next track starts counting afresh
+            if failures >= MAX_FAILURES {
+                session.failures = 0;
+            }
+            (failures, retried, session.text_channel)
+        });
+
+        let outcome = if upcoming && retried {
+            self.remove(track);
+            "Skipped it after retrying once".to_owned()
+        } else if upcoming {
+            match self.replace(track).await {
+                Ok(()) => "Retrying it once".to_owned(),
+                Err(why) => {
+                    warn!("could not retry track {url}: {why:?}");
+                    self.remove(track);
+                    "Skipped it".to_owned()
+                }
+            }
+        } else if failures >= MAX_FAILURES {
+            self.queue.stop();
+            format!("Stopped playback after {failures} tracks failed in a row")
+        } else if retried {
+            "Skipped it after retrying once".to_owned()
+        } else {
+            match self.retry(track, track_data.position()).await {
+                Ok(()) => "Retrying it once".to_owned(),
+                Err(why) => {
+                    warn!("could not retry track {url}: {why:?}");
+                    "Skipped it".to_owned()
+                }
+            }
+        };
+
+        if let Some(channel) = channel {
+            let notice = format!("Could not play **{title}**: {why}\n{outcome}");
+            channel.say(&self.http, notice).await?;
+        }
+        Ok(())
+    }
+
+    /// Plays a fresh copy of a failed track ahead of the rest of the queue.
+    async fn retry(&self, failed: &TrackHandle, position: Duration) -> Result<()> {
+        // the queue has already moved on, so hold the next track while the retry is resolved
+        let next = self.queue.current();
+        if let Some(next) = &next {
+            next.pause()?;
+        }
+
+        let track_data = failed.data::<TrackData>();
+        let url = track_data
+            .metadata
+            .source_url
+            .as_deref()
+            .unwrap_or_default();
+        let resolved = match self.data.resolvers.resolve(url).await {
+            Ok(resolved) => resolved,
+            Err(why) => {
+                if let Some(next) = &next {
+                    next.play()?;
+                }
+                return Err(why);
+            }
+        };
+
+        let Some(handler_lock) = self.data.songbird.get(self.guild_id) else {
+            return Ok(());
+        };
+        let mut handler = handler_lock.lock().await;
+        let live = track_data.live;
+        let track = guild_track(&self.data, self.guild_id, resolved.input, track_data);
+        let handle = handler.enqueue(track).await;
+        drop(handler);
+
+        self.queue.modify_queue(|tracks| {
+            let track = tracks.pop_back().unwrap();
+            tracks.insert(0, track);
+        });
+        self.data.sessions.with(self.guild_id, |session| {
+            session.retried = Some(handle.uuid())
+        });
+
+        if next.is_some() {
+            handle.play()?;
+        }
+        if !live && position > Duration::ZERO {
+            drop(handle.seek(position));
+        }
+        Ok(())
+    }
+
+    /// Swaps an upcoming track which failed for a fresh copy in the same place.
+    async fn replace(&self, failed: &TrackHandle) -> Result<()> {
+        let track_data = failed.data::<TrackData>();
+        let url = track_data
+            .metadata
+            .source_url
+            .as_deref()
+            .unwrap_or_default();
+        let resolved = self.data.resolvers.resolve(url).await?;
+
+        let Some(handler_lock) = self.data.songbird.get(self.guild_id) else {
+            return Ok(());
+        };
+        let mut handler = handler_lock.lock().await;
+        let track = guild_track(&self.data, self.guild_id, resolved.input, track_data);
+        let handle = handler.enqueue(track).await;
+        drop(handler);
+
+        self.queue.modify_queue(|tracks| {
+            let track = tracks.pop_back().unwrap();
+            match tracks
+                .iter()
+                .position(|queued| queued.uuid() == failed.uuid())
+            {
+                Some(index) => tracks[index] = track,
+                // removed from the queue while the copy was resolved
+                None => drop(track.stop()),
+            }
+        });
+        self.data.sessions.with(self.guild_id, |session| {
+            session.retried = Some(handle.uuid())
+        });
+        Ok(())
+    }
+
+    /// Takes an upcoming track which failed out of the queue.
+    fn remove(&self, failed: &TrackHandle) {
+        self.queue
+            .modify_queue(|tracks| tracks.retain(|queued| queued.uuid() != failed.uuid()));
+    }
+}
+
+#[async_trait]
+impl EventHandler for FailureHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        for (state, track) in *tracks {
+            match state.playing {
+                PlayMode::End | PlayMode::Stop if !state.play_time.is_zero() => {
+                    self.data
+                        .sessions
+                        .with(self.guild_id, |session| session.failures = 0);
+                }
+                _ => {
+                    if let Err(why) = self.failed(track, state).await {
+                        warn!("could not handle failed track: {why:?}");
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Keeps the music going with a related track once the last track of the queue ends.
+///
+/// Only natural ends count, so clearing or skipping through the queue still stops playback.
+struct AutoplayHandler {
+    guild_id: serenity::GuildId,
+    queue: TrackQueue,
+    data: Arc<Data>,
+}
+
+impl AutoplayHandler {
+    async fn autoplay(&self, ended: &TrackHandle) -> Result<()> {
+        let Some(seed) = ended.data::<TrackData>().metadata.source_url.clone() else {
+            return Ok(());
+        };
+
+        // skip anything played recently, including the track which just ended
+        let mut played: HashSet<String> = self
+            .data
+            .history
+            .recent(self.guild_id)
+            .await?
+            .into_iter()
+            .map(|entry| entry.url)
+            .collect();
+        played.insert(seed.clone());
+
+        let related = self.data.innertube.related(&seed).await?;
+        let Some(url) = related.iter().find(|url| !played.contains(*url)) else {
+            warn!("no unplayed related tracks for {seed}");
+            return Ok(());
+        };
+
+        let resolved = self.data.resolvers.resolve(url).await?;
+        let (input, track_data) = TrackData::new(resolved, None);
+        let track_data = Arc::new(TrackData {
+            autoplayed: true,
+            ..track_data
+        });
+
+        let Some(handler_lock) = self.data.songbird.get(self.guild_id) else {
+            return Ok(());
+        };
+        let mut handler = handler_lock.lock().await;
+        // something may have been requested while the related track was resolved
+        if handler.queue().is_empty() {
+            let track = guild_track(&self.data, self.guild_id, input, track_data);
+            handler.enqueue(track).await;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for AutoplayHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        let (autoplay, repeat) = self
+            .data
+            .sessions
+            .with(self.guild_id, |session| (session.autoplay, session.repeat));
+        // repeating puts the track back into the queue, so there is nothing to fill
+        if !autoplay || repeat != RepeatMode::Off || !self.queue.is_empty() {
+            return None;
+        }
+
+        let ended = tracks
+            .iter()
+            .find(|(state, _)| matches!(state.playing, PlayMode::End));
+        if let Some((_, track)) = ended {
+            if let Err(why) = self.autoplay(track).await {
+                warn!("could not autoplay after track: {why:?}");
+            }
+        }
+        None
+    }
+}
+
+/// Leaves the voice channel once the queue has been empty for the guild's idle timeout.
+struct IdleChecker {
+    guild_id: serenity::GuildId,
+    queue: TrackQueue,
+    http: Arc<serenity::Http>,
+    data: Arc<Data>,
+}
+
+impl IdleChecker {
+    async fn check(&self) -> Result<()> {
+        let guild_id = self.guild_id.get();
+        let settings = self
+            .data
+            .store
+            .blocking(move |store| store.guild_settings(guild_id))
+            .await?;
+        let timeout = Duration::from_secs(settings.idle_timeout);
+        let idle = self.queue.is_empty();
+
+        let expired = self.data.sessions.with(self.guild_id, |session| {
+            if !idle {
+                session.idle_since = None;
+                return false;
+            }
+            let since = session.idle_since.get_or_insert_with(Instant::now);
+            since.elapsed() >= timeout
+        });
+
+        if expired && !settings.always_on {
+            // leaving shuts down the driver running this handler, so do it elsewhere
+            let (data, http, guild_id) = (self.data.clone(), self.http.clone(), self.guild_id);
+            tokio::spawn(async move {
+                auto_leave(
+                    &data,
+                    &http,
+                    guild_id,
+                    "Left the voice channel after idling",
+                )
+                .await;
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for IdleChecker {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if let Err(why) = self.check().await {
+            warn!(
+                "could not check idle state of guild {}: {why:?}",
+                self.guild_id
+            );
+        }
+        None
+    }
+}
+
+/// Starts or cancels leaving a guild's voice channel depending on whether anyone is listening.
+///
+/// Called on every voice state update of the guild.
+pub async fn check_alone(
+    ctx: &serenity::Context,
+    data: Arc<Data>,
+    guild_id: serenity::GuildId,
+) -> Result<()> {
+    if data.songbird.get(guild_id).is_none() {
+        return Ok(());
+    }
+
+    let settings = data
+        .store
+        .blocking(move |store| store.guild_settings(guild_id.get()))
+        .await?;
+    if settings.always_on {
+        return Ok(());
+    }
+
+    let Some(listeners) = listeners(ctx, guild_id) else {
+        return Ok(());
+    };
+    let alone = listeners.is_empty();
+
+    let started = data.sessions.with(guild_id, |session| {
+        if !alone {
+            session.alone_since = None;
+            return false;
+        }
+        if session.alone_since.is_some() {
+            return false;
+        }
+        session.alone_since = Some(Instant::now());
+        true
+    });
+
+    if started {
+        let timeout = Duration::from_secs(settings.alone_timeout);
+        let http = ctx.http.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            let still_alone = data.sessions.with(guild_id, |session| {
+                session
+                    .alone_since
+                    .is_some_and(|since| since.elapsed() >= timeout)
+            });
+            if still_alone {
+                auto_leave(
+                    &data,
+                    &http,
+                    guild_id,
+                    "Left the voice channel since everyone left",
+                )
+                .await;
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Returns the users, excluding bots, in the bot's voice channel of a guild.
+fn listeners(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+) -> Option<Vec<serenity::UserId>> {
+    let guild = ctx.cache.guild(guild_id)?;
+    let bot_id = ctx.cache.current_user().id;
+    let bot_vc = guild
+        .voice_states
+        .get(&bot_id)
+        .and_then(|state| state.channel_id)?;
+
+    let listeners = guild
+        .voice_states
+        .iter()
+        .filter(|state| {
+            state.channel_id == Some(bot_vc)
+                && state.user_id != bot_id
+                && !state
+                    .member
+                    .as_ref()
+                    .is_some_and(|member| member.user.bot())
+        })
+        .map(|state| state.user_id)
+        .collect();
+    Some(listeners)
+}
+
+/// Whether a member may control playback directly.
+fn is_dj(member: &serenity::Member, settings: &GuildSettings) -> bool {
+    let manager = member
+        .permissions
+        .is_some_and(|permissions| permissions.manage_guild());
+    let has_role = settings
+        .dj_role
+        .is_some_and(|role| member.roles.contains(&serenity::RoleId::new(role)));
+    dj_rights(settings, manager, has_role)
+}
+
+/// Whether someone with or without the right to manage the server and the DJ role is a DJ.
+///
+/// Without a DJ role that is everyone, unless skips go by vote, which anyone skipping directly
+/// would defeat. Members who can manage the server always count as DJs.
+fn dj_rights(settings: &GuildSettings, manager: bool, has_role: bool) -> bool {
+    match settings.dj_role {
+        Some(_) => manager || has_role,
+        None => manager || !settings.vote_skip,
+    }
+}
+
+/// Votes it takes to skip a track, a percentage of the listeners but always at least one.
+fn votes_needed(listeners: usize, threshold: u8) -> usize {
+    (listeners * usize::from(threshold)).div_ceil(100).max(1)
+}
+
+/// Replies to the author and returns false if they are not allowed to control playback.
+async fn require_dj(ctx: Context<'_>) -> Result<bool> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let settings = ctx
+        .data()
+        .store
+        .blocking(move |store| store.guild_settings(guild_id))
+        .await?;
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+
+    if is_dj(&member, &settings) {
+        return Ok(true);
+    }
+    ctx.say_ephemeral("Only DJs can do that").await?;
+    Ok(false)
+}
+
+/// Skips the current track, or counts a vote to skip it when the member may not skip directly.
+///
+/// Returns the message to reply with.
+async fn skip_or_vote(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    member: &serenity::Member,
+) -> Result<String> {
+    let Some(handler_lock) = data.songbird.get(guild_id) else {
+        return Ok("Not in a voice channel".to_owned());
+    };
+    let queue = handler_lock.lock().await.queue().clone();
+    let Some(current) = queue.current() else {
+        return Ok("Nothing is playing right now".to_owned());
+    };
+
+    let settings = data
+        .store
+        .blocking(move |store| store.guild_settings(guild_id.get()))
+        .await?;
+    let requester = current.data::<TrackData>().requester;
+    if is_dj(member, &settings) || requester == Some(member.user.id) {
+        queue.skip()?;
+        return Ok("Skipped track".to_owned());
+    }
+    if !settings.vote_skip {
+        return Ok("Only DJs can do that".to_owned());
+    }
+
+    let listeners = listeners(ctx, guild_id).unwrap_or_default();
+    if !listeners.contains(&member.user.id) {
+        return Ok("You are not in my voice channel".to_owned());
+    }
+
+    let (votes, needed) = data.sessions.with(guild_id, |session| {
+        // votes only count for the track they were cast on
+        if session.skip_votes.0 != Some(current.uuid()) {
+            session.skip_votes = (Some(current.uuid()), HashSet::new());
+        }
+
+        let votes = &mut session.skip_votes.1;
+        votes.insert(member.user.id);
+        votes.retain(|voter| listeners.contains(voter));
+
+        let needed = votes_needed(listeners.len(), settings.vote_threshold);
+        (votes.len(), needed)
+    });
+
+    if votes >= needed {
+        queue.skip()?;
+        Ok(format!("Vote passed ({votes}/{needed}), skipped track"))
+    } else {
+        Ok(format!("Voted to skip ({votes}/{needed})"))
+    }
+}
+
+/// Leaves the voice channel of a guild and posts a notice where music was requested.
+async fn auto_leave(data: &Data, http: &serenity::Http, guild_id: serenity::GuildId, notice: &str) {
+    if data.songbird.get(guild_id).is_none() {
+        return;
+    }
+
+    let channel = data.sessions.with(guild_id, |session| session.text_channel);
+    if let Err(why) = leave_guild(data, guild_id).await {
+        warn!("could not leave guild {guild_id}: {why:?}");
+        return;
+    }
+
+    if let Some(channel) = channel {
+        channel.say(http, notice).await.ok();
+    }
+}
+
+/// Leaves the voice channel of a guild and forgets its session and saved queue.
+pub async fn leave_guild(data: &Data, guild_id: serenity::GuildId) -> Result<()> {
+    data.songbird.remove(guild_id).await?;
+    data.sessions.end(guild_id);
+    data.store
+        .blocking(move |store| store.delete_player(guild_id.get()))
+        .await
+}
+
+/// Starts the session of a guild and registers its event handlers on a freshly joined call.
+///
+/// The settings are loaded by the caller before it locks the call.
+fn start_player(
+    handler: &mut Call,
+    settings: GuildSettings,
+    data: Arc<Data>,
+    guild_id: serenity::GuildId,
+    voice_channel: serenity::ChannelId,
+    text_channel: serenity::ChannelId,
+    http: Arc<serenity::Http>,
+) {
+    let filters = Arc::new(Filters::default());
+    filters.set_normalize(settings.normalize);
+    data.sessions.start(
+        guild_id,
+        Session {
+            volume: f32::from(settings.volume) / 100.0,
+            filters,
+            crossfade: Duration::from_secs(settings.crossfade),
+            fade_curve: settings.fade_curve,
+            text_channel: Some(text_channel),
+            ..Session::default()
+        },
+    );
+
+    handler.add_global_event(
+        Event::Track(TrackEvent::Play),
+        PlayerUpdater {
+            guild_id,
+            queue: handler.queue().clone(),
+            http: http.clone(),
+            data: data.clone(),
+            track_change: true,
+        },
+    );
+    handler.add_global_event(
+        Event::Periodic(PLAYER_REFRESH, None),
+        PlayerUpdater {
+            guild_id,
+            queue: handler.queue().clone(),
+            http: http.clone(),
+            data: data.clone(),
+            track_change: false,
+        },
+    );
+    for event in [TrackEvent::Error, TrackEvent::End] {
+        handler.add_global_event(
+            Event::Track(event),
+            FailureHandler {
+                guild_id,
+                queue: handler.queue().clone(),
+                http: http.clone(),
+                data: data.clone(),
+            },
+        );
+    }
+    handler.add_global_event(
+        Event::Periodic(Duration::from_secs(15), None),
+        IdleChecker {
+            guild_id,
+            queue: handler.queue().clone(),
+            http,
+            data: data.clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Track(TrackEvent::End),
+        RepeatHandler {
+            guild_id,
+            queue: handler.queue().clone(),
+            data: data.clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Track(TrackEvent::Play),
+        TrackLooper {
+            guild_id,
+            data: data.clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Track(TrackEvent::End),
+        AutoplayHandler {
+            guild_id,
+            queue: handler.queue().clone(),
+            data: data.clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Track(TrackEvent::End),
+        HistoryRecorder {
+            guild_id,
+            data: data.clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Periodic(SEGMENT_CHECK, None),
+        SegmentSkipper {
+            guild_id,
+            queue: handler.queue().clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Periodic(FADE_TICK, None),
+        Crossfader {
+            guild_id,
+            queue: handler.queue().clone(),
+            data: data.clone(),
+        },
+    );
+    handler.add_global_event(
+        Event::Periodic(Duration::from_secs(10), None),
+        QueueSaver {
+            guild_id,
+            voice_channel,
+            text_channel,
+            queue: handler.queue().clone(),
+            data,
+        },
+    );
+}
+
+/// Creates a track which follows the guild's session settings, including its filters.
+fn guild_track(
+    data: &Data,
+    guild_id: serenity::GuildId,
+    input: Input,
+    track_data: Arc<TrackData>,
+) -> Track {
+    let (volume, filters) = data.sessions.with(guild_id, |session| {
+        (session.volume, session.filters.clone())
+    });
+    let input = match input {
+        Input::Lazy(source) => Input::Lazy(Box::new(Filtered::new(
+            source,
+            filters,
+            track_data.clock.clone(),
+            track_data.loudness,
+            track_data.clip.map_or(Duration::ZERO, |clip| clip.start),
+            // durations are rounded, so the stream is only cut off when it ends early
+            track_data
+                .end()
+                .filter(|end| Some(*end) != track_data.metadata.duration),
+        ))),
+        input => input,
+    };
+    Track::new_with_data(input, track_data).volume(volume)
+}
+
+/// Rejoins the voice channels and rebuilds the queues saved before the last shutdown.
+pub async fn restore_players(ctx: &serenity::Context, data: &Data) -> Result<()> {
+    for saved in data.store.blocking(Store::load_players).await? {
+        if let Err(why) = restore_player(ctx, data, &saved).await {
+            warn!(
+                "could not restore player for guild {}: {why:?}",
+                saved.guild_id
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn restore_player(ctx: &serenity::Context, data: &Data, saved: &SavedPlayer) -> Result<()> {
+    let guild_id = serenity::GuildId::new(saved.guild_id);
+    let voice_channel = serenity::ChannelId::new(saved.voice_channel);
+    let text_channel = serenity::ChannelId::new(saved.text_channel);
+
+    // resolving takes a request or more per track, so it happens before the call is locked
+    let resolving: Vec<_> = saved
+        .tracks
+        .iter()
+        .map(|track| {
+            let data = ctx.data::<Data>();
+            let url = track.url.clone();
+            tokio::spawn(async move { data.resolvers.resolve(&url).await })
+        })
+        .collect();
+    let mut tracks = Vec::new();
+    for (index, (track, resolving)) in saved.tracks.iter().zip(resolving).enumerate() {
+        match resolving.await? {
+            Ok(resolved) => tracks.push((index, track, resolved)),
+            Err(why) => warn!("could not restore track {}: {why:?}", track.url),
+        }
+    }
+    // the saved position belongs to the first saved track, which may be the one that failed
+    let resume = tracks.first().is_some_and(|(index, ..)| *index == 0);
+
+    let settings = data
+        .store
+        .blocking(move |store| store.guild_settings(guild_id.get()))
+        .await?;
+    let handler_lock = data.songbird.join(guild_id, voice_channel).await?;
+    let mut handler = handler_lock.lock().await;
+    start_player(
+        &mut handler,
+        settings,
+        ctx.data::<Data>(),
+        guild_id,
+        voice_channel,
+        text_channel,
+        ctx.http.clone(),
+    );
+    data.sessions
+        .with(guild_id, |session| session.repeat = saved.repeat);
+
+    for (_, track, mut resolved) in tracks {
+        let requester = track.requester.map(serenity::UserId::new);
+        resolved.clip = track.clip.or(resolved.clip);
+        let (input, mut track_data) = TrackData::new(resolved, requester);
+        track_data.autoplayed = track.autoplayed;
+        handler
+            .enqueue(guild_track(data, guild_id, input, Arc::new(track_data)))
+            .await;
+    }
+
+    if let Some(current) = handler.queue().current().filter(|_| resume) {
+        // live streams cannot seek and pick up wherever they are now anyway
+        let live = current.data::<TrackData>().live;
+        if saved.position > Duration::ZERO && !live {
+            drop(current.seek(saved.position));
+        }
+    }
+    Ok(())
+}
+
+pub fn commands() -> [Command; 28] {
+    [
+        play(),
+        search(),
+        set_loop(),
+        autoplay(),
+        clear(),
+        skip(),
+        pause(),
+        nowplaying(),
+        seek(),
+        forward(),
+        rewind(),
+        volume(),
+        resume(),
+        leave(),
+        queue(),
+        playnext(),
+        remove(),
+        move_track(),
+        swap(),
+        shuffle(),
+        skipto(),
+        history(),
+        previous(),
+        replay(),
+        library::library(),
+        filter::filter(),
+        chapters::chapters(),
+        chapters::chapter(),
+    ]
+}
+
+/// Play some music
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn play(
+    ctx: Context<'_>,
+    #[description = "url or term"]
+    #[autocomplete = "autocomplete_song"]
+    song: String,
+    #[description = "choose from the search results"] pick: Option<bool>,
+    #[description = "timestamp to start at, such as 1:23"] start: Option<String>,
+    #[description = "timestamp to stop at, such as 2:34"] end: Option<String>,
+) -> Result<()> {
+    let (start, end) = match (
+        start.as_deref().map(parse_timestamp),
+        end.as_deref().map(parse_timestamp),
+    ) {
+        (Some(None), _) | (_, Some(None)) => {
+            ctx.say_ephemeral("Invalid timestamp, use 1:23, 01:02:03, 1m23s or plain seconds")
+                .await?;
+            return Ok(());
+        }
+        (start, end) => (start.flatten(), end.flatten()),
+    };
+    ctx.defer().await?;
+
+    let resolver = if is_link(&song) {
+        let Some(resolver) = ctx.data().resolvers.find(&song) else {
+            ctx.say_ephemeral("Unsupported link").await?;
+            return Ok(());
+        };
+        Some(resolver)
+    } else {
+        None
+    };
+
+    // join first so nothing is fetched for someone who is not in a voice channel
+    let Some(handler_lock) = join_author_channel(ctx).await? else {
+        return Ok(());
+    };
+
+    if let Some(resolver) = resolver {
+        // links to a single track are handled like search results below
+        let entries = resolver.entries(&song).await?;
+        if entries != [song.as_str()] {
+            return enqueue_playlist(ctx, &handler_lock, resolver, entries).await;
+        }
+    }
+
+    let Some(mut resolved) = resolve_song(ctx, &song, pick.unwrap_or(false)).await? else {
+        return Ok(());
+    };
+
+    // the range given to the command takes precedence over the one in the link
+    if start.is_some() || end.is_some() {
+        let linked = resolved.clip.unwrap_or(Clip {
+            start: Duration::ZERO,
+            end: None,
+        });
+        resolved.clip = Some(Clip {
+            start: start.unwrap_or(linked.start),
+            end: end.or(linked.end),
+        });
+    }
+    if let Some(Err(why)) = resolved
+        .clip
+        .map(|clip| check_clip(&clip, &resolved.metadata, resolved.live))
+    {
+        ctx.say_ephemeral(why).await?;
+        return Ok(());
+    }
+
+    enqueue_source(ctx, &handler_lock, resolved).await
+}
+
+/// Resolves a url or search term into a source, replying to the author if nothing was found.
+async fn resolve_song(ctx: Context<'_>, song: &str, pick: bool) -> Result<Option<Resolved>> {
+    let data = ctx.data();
+    let resolvers = &data.resolvers;
+
+    if is_link(song) {
+        return match resolvers.resolve(song).await {
+            Ok(resolved) => Ok(Some(resolved)),
+            Err(why) if why.is::<UnsupportedLink>() => {
+                warn!("could not play {song}: {why:?}");
+                ctx.say_ephemeral("Unsupported link").await?;
+                Ok(None)
+            }
+            Err(why) => Err(why),
+        };
+    }
+
+    if pick {
+        return pick_result(ctx, song).await;
+    }
+
+    let mut results = resolvers.search(song).await?;
+    if results.is_empty() {
+        ctx.say_ephemeral(format!("No results found for {song}."))
+            .await?;
+        return Ok(None);
+    }
+    let url = results.swap_remove(0);
+    Ok(Some(resolvers.resolve(&url).await?))
+}
+
+/// Suggests search results while the song argument is being typed.
+async fn autocomplete_song<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> serenity::CreateAutocompleteResponse<'a> {
+    let response = serenity::CreateAutocompleteResponse::new();
+    let data = ctx.data();
+    let cache = &data.search_cache;
+
+    if partial.len() < 3 || is_link(partial) {
+        return response;
+    }
+
+    let results = match cache.get(partial) {
+        Some(results) => results,
+        None => {
+            if !cache.debounce(ctx.author().id.get()).await {
+                return response;
+            }
+
+            let Ok(mut urls) = data.resolvers.search(partial).await else {
+                return response;
+            };
+            urls.truncate(PICK_RESULTS);
+
+            let resolving: Vec<_> = urls
+                .into_iter()
+                .map(|url| {
+                    let data = data.clone();
+                    tokio::spawn(async move { data.resolvers.metadata(&url).await })
+                })
+                .collect();
+
+            let mut results = Vec::with_capacity(resolving.len());
+            for task in resolving {
+                if let Ok(Ok(metadata)) = task.await {
+                    results.push(metadata);
+                }
+            }
+            cache.insert(partial, results)
+        }
+    };
+
+    let choices = results
+        .iter()
+        .filter_map(|metadata| {
+            let url = metadata.source_url.clone()?;
+            let label = format!(
+                "{} - {} ({})",
+                metadata.title.as_deref().unwrap_or("No Title"),
+                metadata.channel.as_deref().unwrap_or("No Channel"),
+                duration_hhmmss(&metadata.duration.unwrap_or_default()),
+            );
+            Some(serenity::AutocompleteChoice::new(choice_label(label), url))
+        })
+        .collect::<Vec<_>>();
+
+    response.set_choices(choices)
+}
+
+/// Search for a track and pick which result to play
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn search(ctx: Context<'_>, #[description = "search term"] query: String) -> Result<()> {
+    ctx.defer().await?;
+
+    let Some(handler_lock) = join_author_channel(ctx).await? else {
+        return Ok(());
+    };
+
+    let Some(resolved) = pick_result(ctx, &query).await? else {
+        return Ok(());
+    };
+
+    enqueue_source(ctx, &handler_lock, resolved).await
+}
+
+/// Joins the author's voice channel if the bot is not in one yet.
+///
+/// Returns `None` after replying to the author if they are not in the bot's voice channel.
+async fn join_author_channel(ctx: Context<'_>) -> Result<Option<Arc<Mutex<Call>>>> {
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    let user_vc = ctx
+        .guild()
+        .unwrap()
+        .voice_states
+        .get(&ctx.author().id)
+        .and_then(|voice_state| voice_state.channel_id);
+
+    let Some(user_vc) = user_vc else {
+        ctx.say_ephemeral("You are not in a voice channel").await?;
+        return Ok(None);
+    };
+
+    // join the user's channel if we are currently not in one, the settings are only needed then
+    let mut joined = None;
+    let handler_lock = if let Some(handler) = songbird.get(guild_id) {
+        handler
+    } else {
+        let settings = ctx
+            .data()
+            .store
+            .blocking(move |store| store.guild_settings(guild_id.get()))
+            .await?;
+        joined = Some(settings);
+        songbird.join(guild_id, user_vc).await?
+    };
+
+    let mut handler = handler_lock.lock().await;
+    let bot_vc = handler.current_channel().unwrap();
+    if bot_vc != user_vc.into() {
+        ctx.say_ephemeral("You are not in my voice channel").await?;
+        return Ok(None);
+    }
+
+    if let Some(settings) = joined {
+        start_player(
+            &mut handler,
+            settings,
+            ctx.serenity_context().data::<Data>(),
+            guild_id,
+            user_vc,
+            ctx.channel_id(),
+            ctx.serenity_context().http.clone(),
+        );
+    }
+
+    drop(handler);
+    Ok(Some(handler_lock))
+}
+
+/// Enqueues a single resolved source and tells the author where it ended up.
+///
+/// The call is only locked to enqueue, so other commands are not held up by the reply.
+async fn enqueue_source(
+    ctx: Context<'_>,
+    handler_lock: &Mutex<Call>,
+    mut resolved: Resolved,
+) -> Result<()> {
+    let artwork = resolved.artwork.take();
+    let (input, data) = TrackData::new(resolved, Some(ctx.author().id));
+    let data = Arc::new(data);
+
+    let track = guild_track(&ctx.data(), ctx.guild_id().unwrap(), input, data.clone());
+    let len = {
+        let mut handler = handler_lock.lock().await;
+        let len = handler.queue().len();
+        handler.enqueue(track).await;
+        len
+    };
+    if len > 0 {
+        let mut embed = track_embed("Enqueued", &data).field(
+            "Position",
+            format!("#{} in queue", len + 1),
+            false,
+        );
+        let mut reply = poise::CreateReply::default();
+        if let Some(artwork) = artwork {
+            embed = embed.thumbnail("attachment://artwork.jpg");
+            reply = reply.attachment(serenity::CreateAttachment::bytes(artwork, "artwork.jpg"));
+        }
+        ctx.send(reply.embed(embed)).await?;
+    } else {
+        ctx.say("Track added".to_owned()).await?;
+    }
+    Ok(())
+}
+
+/// Shows the top search results as buttons and lets the author pick one.
+///
+/// Returns `None` if nothing was found or the author did not choose in time.
+async fn pick_result(ctx: Context<'_>, query: &str) -> Result<Option<Resolved>> {
+    let data = ctx.data();
+    let mut results = data.resolvers.search(query).await?;
+    results.truncate(PICK_RESULTS);
+
+    // resolve concurrently since every result needs its own metadata request
+    let resolving: Vec<_> = results
+        .into_iter()
+        .map(|url| {
+            let data = data.clone();
+            tokio::spawn(async move { data.resolvers.resolve(&url).await })
+        })
+        .collect();
+
+    let mut choices = Vec::with_capacity(resolving.len());
+    for task in resolving {
+        if let Ok(resolved) = task.await? {
+            choices.push(resolved);
+        }
+    }
+
+    if choices.is_empty() {
+        ctx.say_ephemeral(format!("No results found for {query}."))
+            .await?;
+        return Ok(None);
+    }
+
+    let ctx_id = ctx.id();
+    let description = choices
+        .iter()
+        .enumerate()
+        .map(|(i, Resolved { metadata, .. })| {
+            format!(
+                "{}. **{}** - {} ({})",
+                i + 1,
+                metadata.title.as_deref().unwrap_or("No Title"),
+                metadata.channel.as_deref().unwrap_or("No Channel"),
+                duration_hhmmss(&metadata.duration.unwrap_or_default()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let reply = {
+        let buttons = serenity::CreateActionRow::Buttons(
+            (0..choices.len())
+                .map(|i| {
+                    serenity::CreateButton::new(format!("{ctx_id}pick{i}"))
+                        .label((i + 1).to_string())
+                })
+                .collect(),
+        );
+
+        let embed = serenity::CreateEmbed::default()
+            .title(format!("Results for {query}"))
+            .description(description)
+            .footer(serenity::CreateEmbedFooter::new(format!(
+                "Choose within {} seconds",
+                PICK_TIMEOUT.as_secs()
+            )));
+
+        CreateReply::default()
+            .embed(embed)
+            .components(vec![buttons])
+    };
+
+    let handle = ctx.send(reply).await?;
+
+    let shard = &ctx.serenity_context().shard;
+    let press = serenity::collector::ComponentInteractionCollector::new(shard.clone())
+        .author_id(ctx.author().id)
+        .filter(move |press| press.data.custom_id.starts_with(&format!("{ctx_id}pick")))
+        .timeout(PICK_TIMEOUT)
+        .await;
+
+    let Some(press) = press else {
+        handle
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("No track was picked in time")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(None);
+    };
+
+    let index = press.data.custom_id[format!("{ctx_id}pick").len()..].parse::<usize>()?;
+    let picked = choices.swap_remove(index);
+
+    let title = format!(
+        "Picked **{}**",
+        picked.metadata.title.as_deref().unwrap_or("No Title")
+    );
+    press
+        .create_response(
+            ctx.http(),
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(title)
+                    .embeds(vec![])
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    Ok(Some(picked))
+}
+
+/// Enqueues every entry of a playlist, skipping entries which cannot be played.
+async fn enqueue_playlist(
+    ctx: Context<'_>,
+    handler_lock: &Mutex<Call>,
+    resolver: Arc<dyn SourceResolver>,
+    mut ids: Vec<String>,
+) -> Result<()> {
+    let data = ctx.data();
+    if ids.is_empty() {
+        ctx.say_ephemeral("That playlist is empty or private")
+            .await?;
+        return Ok(());
+    }
+
+    let over_limit = ids.len().saturating_sub(data.playlist_limit);
+    ids.truncate(data.playlist_limit);
+
+    // resolve entries concurrently but keep the playlist order
+    let resolving: Vec<_> = ids
+        .into_iter()
+        .map(|id| {
+            let resolver = resolver.clone();
+            tokio::spawn(async move {
+                let source = resolver.resolve(&id).await;
+                (id, source)
+            })
+        })
+        .collect();
+
+    let requester = ctx.author().name.to_string();
+    let mut enqueued = 0;
+    let mut total = Duration::ZERO;
+    let mut unavailable = Vec::new();
+    for task in resolving {
+        let (id, source) = task.await?;
+        let resolved = match source {
+            Ok(resolved) => resolved,
+            Err(why) => {
+                warn!("skipping playlist entry {id}: {why:?}");
+                unavailable.push(id);
+                continue;
+            }
+        };
+
+        total += resolved.metadata.duration.unwrap_or_default();
+        let (input, track_data) = TrackData::new(resolved, Some(ctx.author().id));
+        let track = guild_track(&data, ctx.guild_id().unwrap(), input, Arc::new(track_data));
+        // the call is only locked to enqueue, resolving the rest of the playlist takes a while
+        handler_lock.lock().await.enqueue(track).await;
+        enqueued += 1;
+    }
+
+    let mut summary = format!(
+        "Enqueued {enqueued} tracks, {} total",
+        duration_hhmmss(&total)
+    );
+    if !unavailable.is_empty() {
+        summary += &format!(", {} unavailable skipped", unavailable.len());
+    }
+
+    let mut embed = serenity::CreateEmbed::default()
+        .title("Playlist Enqueued")
+        .description(summary)
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "Requested by {requester}"
+        )));
+    if !unavailable.is_empty() {
+        let skipped = unavailable
+            .iter()
+            .take(10)
+            .map(|id| format!("`{id}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        embed = embed.field("Unavailable", skipped, false);
+    }
+    if over_limit > 0 {
+        embed = embed.field(
+            "Limit",
+            format!(
+                "Only the first {} entries were imported, {over_limit} were left out",
+                data.playlist_limit
+            ),
+            false,
+        );
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Disconnect from the voice channel and clear the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn leave(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    if songbird.get(guild_id).is_some() {
+        leave_guild(&ctx.data(), guild_id).await?;
+        ctx.say("Leaving the channel").await?;
+    } else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+    }
+    Ok(())
+}
+
+/// Gets information of the currect track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn nowplaying(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    let Some(handler_lock) = songbird.get(guild_id) else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    };
+
+    let handler = handler_lock.lock().await;
+    let current = handler.queue().current();
+
+    if let Some(track) = current {
+        let data = track.data::<TrackData>();
+        let metadata = &data.metadata;
+
+        let Ok(trackstate) = track.get_info().await else {
+            ctx.say_ephemeral("Oops something went wrong...").await?;
+            return Ok(());
+        };
+
+        let embed = if data.live {
+            let title = stream_title(&ctx.data(), &data).await;
+            live_fields(
+                track_embed("Now Playing", &data),
+                &trackstate.position,
+                title,
+            )
+        } else {
+            let position = data.position();
+            let embed = track_embed("Now Playing", &data).field(
+                "Progress",
+                progress_field(&position, metadata.duration),
+                false,
+            );
+            let embed = chapter_field(embed, &data, &position);
+            match data.end() {
+                Some(end) => {
+                    // the speed filter plays what is left of the source faster or slower
+                    let speed = ctx
+                        .data()
+                        .sessions
+                        .with(guild_id, |session| session.filters.get().speed)
+                        .max(0.1);
+                    let left = time_left(end, position, &data.segments).div_f32(speed);
+                    embed.footer(serenity::CreateEmbedFooter::new(format!(
+                        "{} left in track",
+                        duration_hhmmss(&left)
+                    )))
+                }
+                None => embed,
+            }
+        };
+        ctx.send(CreateReply::default().embed(embed)).await?;
+    } else {
+        ctx.say_ephemeral("Nothing is playing right now").await?;
+    }
+    Ok(())
+}
+
+/// Seek to a position in the current track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn seek(
+    ctx: Context<'_>,
+    #[description = "timestamp such as 1:23, 01:02:03, 1m23s or 83"] timestamp: String,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let Some(target) = parse_timestamp(&timestamp) else {
+        ctx.say_ephemeral("Invalid timestamp, use 1:23, 01:02:03, 1m23s or plain seconds")
+            .await?;
+        return Ok(());
+    };
+    seek_current(ctx, |_| target).await
+}
+
+/// Skip ahead in the current track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn forward(
+    ctx: Context<'_>,
+    #[description = "seconds to skip ahead, 10 by default"] seconds: Option<u64>,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let step = seconds.map_or(SEEK_STEP, Duration::from_secs);
+    seek_current(ctx, |position| position + step).await
+}
+
+/// Go back in the current track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn rewind(
+    ctx: Context<'_>,
+    #[description = "seconds to go back, 10 by default"] seconds: Option<u64>,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let step = seconds.map_or(SEEK_STEP, Duration::from_secs);
+    seek_current(ctx, |position| position.saturating_sub(step)).await
+}
+
+/// Seeks the current track to the position computed from its current position.
+async fn seek_current(ctx: Context<'_>, target: impl FnOnce(Duration) -> Duration) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    let Some(handler_lock) = songbird.get(guild_id) else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    };
+
+    let handler = handler_lock.lock().await;
+    let Some(track) = handler.queue().current() else {
+        ctx.say_ephemeral("Nothing is playing right now").await?;
+        return Ok(());
+    };
+    drop(handler);
+
+    let data = track.data::<TrackData>();
+    if data.live {
+        ctx.say_ephemeral("Cannot seek in a live stream").await?;
+        return Ok(());
+    }
+    let duration = data.metadata.duration;
+    // the source never plays before the clip start, so going back further just restarts the clip
+    let start = data.clip.map_or(Duration::ZERO, |clip| clip.start);
+    let position = target(data.position()).max(start);
+    if let Some(end) = data.end().filter(|end| position >= *end) {
+        ctx.say_ephemeral(format!(
+            "Cannot seek to {}, the track plays from {} to {}",
+            duration_hhmmss(&position),
+            duration_hhmmss(&start),
+            duration_hhmmss(&end)
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let position = track.seek_async(position).await?;
+    let embed =
+        track_embed("Seeked", &data).field("Progress", progress_field(&position, duration), false);
+    let embed = chapter_field(embed, &data, &position);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Show or change the volume
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn volume(
+    ctx: Context<'_>,
+    #[description = "volume in percent"]
+    #[min = 0]
+    #[max = 200]
+    volume: Option<u16>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    let Some(handler_lock) = songbird.get(guild_id) else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    };
+
+    let Some(volume) = volume else {
+        let current = ctx.data().sessions.with(guild_id, |session| session.volume);
+        ctx.say(format!("Volume is at {:.0}%", current * 100.0))
+            .await?;
+        return Ok(());
+    };
+
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let level = f32::from(volume) / 100.0;
+    ctx.data()
+        .sessions
+        .with(guild_id, |session| session.volume = level);
+
+    let handler = handler_lock.lock().await;
+    for track in handler.queue().current_queue() {
+        track.set_volume(level)?;
+    }
+    ctx.say(format!("Set volume to {volume}%")).await?;
+    Ok(())
+}
+
+/// Show all tracks in the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn queue(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    let Some(handler_lock) = songbird.get(guild_id) else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    };
+
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue().current_queue();
+    drop(handler);
+
+    if queue.is_empty() {
+        ctx.say_ephemeral("Nothing is in the queue").await?;
+        return Ok(());
+    }
+
+    let pagecount = queue.len().div_ceil(10);
+    let mut pages: Vec<String> = Vec::with_capacity(pagecount);
+    for (i, track) in queue.iter().enumerate() {
+        let metadata = &track.data::<TrackData>().metadata;
+        let title = metadata.title.clone().unwrap_or("~~~~".to_owned());
+        let position = i + 1;
+        if i % 10 == 0 {
+            pages.push(format!("{position}. {title}"));
+        } else {
+            let idx = pages.len() - 1;
+            pages[idx] += &format!("\n{position}. {title}");
+        }
+    }
+
+    paginate(ctx, "Currently Playing", &pages).await?;
+    Ok(())
+}
+
+/// Show the tracks played recently
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn history(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap();
+    let history = ctx.data().history.recent(guild_id).await?;
+
+    if history.is_empty() {
+        ctx.say_ephemeral("Nothing has been played yet").await?;
+        return Ok(());
+    }
+
+    let pages: Vec<String> = history
+        .chunks(10)
+        .enumerate()
+        .map(|(page, entries)| {
+            entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    let played_at = entry
+                        .played_at
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    format!(
+                        "{}. {} - {}, <t:{played_at}:R>",
+                        page * 10 + i + 1,
+                        entry.title,
+                        entry.requester
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+
+    paginate(ctx, "Recently Played", &pages).await?;
+    Ok(())
+}
+
+/// Play the previous track again
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn previous(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    ctx.defer().await?;
+
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(handler_lock) = join_author_channel(ctx).await? else {
+        return Ok(());
+    };
+
+    // the entry is only taken off the history once it could be resolved, so a failure keeps it
+    let recent = ctx.data().history.recent(guild_id).await?;
+    let Some(entry) = recent.into_iter().next() else {
+        ctx.say_ephemeral("Nothing has been played yet").await?;
+        return Ok(());
+    };
+
+    let mut resolved = ctx.data().resolvers.resolve(&entry.url).await?;
+    resolved.clip = entry.clip.or(resolved.clip);
+    let (input, data) = TrackData::new(resolved, Some(ctx.author().id));
+    let data = Arc::new(data);
+
+    let queue = handler_lock.lock().await.queue().clone();
+
+    // the current track cannot be replayed, so it is put back as a fresh copy after the previous one
+    let current = match queue.current() {
+        Some(current) => {
+            let current_data = current.data::<TrackData>();
+            let url = current_data
+                .metadata
+                .source_url
+                .as_deref()
+                .unwrap_or_default();
+            let copy = ctx.data().resolvers.resolve(url).await?;
+            Some((current, copy, current_data))
+        }
+        None => None,
+    };
+    ctx.data().history.pop(guild_id).await?;
+
+    let mut handler = handler_lock.lock().await;
+    let track = guild_track(&ctx.data(), guild_id, input, data.clone());
+    handler.enqueue(track).await;
+
+    if let Some((current, copy, current_data)) = current {
+        let track = guild_track(&ctx.data(), guild_id, copy.input, current_data);
+        handler.enqueue(track).await;
+        drop(handler);
+
+        queue.modify_queue(|tracks| {
+            let copy = tracks.pop_back().unwrap();
+            let previous = tracks.pop_back().unwrap();
+            tracks.insert(1, previous);
+            tracks.insert(2, copy);
+        });
+        ctx.data().sessions.with(guild_id, |session| {
+            session.unrecorded = Some(current.uuid())
+        });
+        queue.skip()?;
+    }
+
+    let embed = track_embed("Playing Previous", &data);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Restart the current track from the beginning
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn replay(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+    let Some(current) = queue.current() else {
+        ctx.say_ephemeral("Nothing is playing right now").await?;
+        return Ok(());
+    };
+
+    current.seek_async(Duration::ZERO).await?;
+    ctx.say("Restarted the current track").await?;
+    Ok(())
+}
+
+/// Play a track right after the current one
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn playnext(
+    ctx: Context<'_>,
+    #[description = "url or term"]
+    #[autocomplete = "autocomplete_song"]
+    song: String,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    ctx.defer().await?;
+
+    let Some(handler_lock) = join_author_channel(ctx).await? else {
+        return Ok(());
+    };
+
+    let Some(resolved) = resolve_song(ctx, &song, false).await? else {
+        return Ok(());
+    };
+
+    let (input, data) = TrackData::new(resolved, Some(ctx.author().id));
+    let data = Arc::new(data);
+
+    let mut handler = handler_lock.lock().await;
+    let track = guild_track(&ctx.data(), ctx.guild_id().unwrap(), input, data.clone());
+    handler.enqueue(track).await;
+    handler.queue().modify_queue(|queue| {
+        if queue.len() > 2 {
+            let track = queue.pop_back().unwrap();
+            queue.insert(1, track);
+        }
+    });
+    drop(handler);
+
+    let embed = track_embed("Playing Next", &data);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Remove a track or a range of tracks such as 3-7 from the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "position or range of positions"] positions: String,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+
+    let Some((start, end)) = parse_range(&positions) else {
+        ctx.say_ephemeral("Invalid position, use a number such as 3 or a range such as 3-7")
+            .await?;
+        return Ok(());
+    };
+
+    // the queue can change until it is locked, so positions are checked against it there
+    let removed = queue.modify_queue(|tracks| -> Result<_, String> {
+        check_range(tracks.len(), start, end)?;
+        // the current track is skipped rather than removed, so the queue moves on to the next one
+        Ok(tracks
+            .drain(start.max(2) - 1..end)
+            .map(|track| {
+                drop(track.stop());
+                track.handle()
+            })
+            .collect::<Vec<_>>())
+    });
+    let removed = match removed {
+        Ok(removed) => removed,
+        Err(why) => {
+            ctx.say_ephemeral(why).await?;
+            return Ok(());
+        }
+    };
+    let skipped = queue.current().filter(|_| start == 1);
+    if skipped.is_some() {
+        queue.skip()?;
+    }
+
+    let Some(first) = skipped.iter().chain(removed.iter()).next() else {
+        ctx.say_ephemeral("Nothing was removed").await?;
+        return Ok(());
+    };
+
+    let count = removed.len() + usize::from(skipped.is_some());
+    let data = first.data::<TrackData>();
+    let mut embed = track_embed("Removed", &data);
+    if count > 1 {
+        embed = embed.field("Tracks", format!("Removed {count} tracks"), false);
+    }
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Move a track to another position in the queue
+#[poise::command(slash_command, category = "Music", guild_only, rename = "move")]
+pub async fn move_track(
+    ctx: Context<'_>,
+    #[description = "position of the track"] from: usize,
+    #[description = "new position of the track"] to: usize,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+
+    let moved = queue.modify_queue(|tracks| -> Result<_, String> {
+        check_upcoming(tracks.len(), from)?;
+        check_upcoming(tracks.len(), to)?;
+        let track = tracks.remove(from - 1).unwrap();
+        let handle = track.handle();
+        tracks.insert(to - 1, track);
+        Ok(handle)
+    });
+    let moved = match moved {
+        Ok(moved) => moved,
+        Err(why) => {
+            ctx.say_ephemeral(why).await?;
+            return Ok(());
+        }
+    };
+
+    let data = moved.data::<TrackData>();
+    let embed = track_embed("Moved", &data).field("Position", format!("#{to} in queue"), false);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Swap the positions of two tracks in the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn swap(
+    ctx: Context<'_>,
+    #[description = "position of the first track"] a: usize,
+    #[description = "position of the second track"] b: usize,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+
+    // after the swap the track from `a` sits at `b` and the other way around
+    let swapped = queue.modify_queue(|tracks| -> Result<_, String> {
+        check_upcoming(tracks.len(), a)?;
+        check_upcoming(tracks.len(), b)?;
+        tracks.swap(a - 1, b - 1);
+        Ok((tracks[b - 1].handle(), tracks[a - 1].handle()))
+    });
+    let (first, second) = match swapped {
+        Ok(swapped) => swapped,
+        Err(why) => {
+            ctx.say_ephemeral(why).await?;
+            return Ok(());
+        }
+    };
+
+    let data = first.data::<TrackData>();
+    let other = second.data::<TrackData>();
+    let other_title = other.metadata.title.as_deref().unwrap_or("No Title");
+    let embed = track_embed("Swapped", &data)
+        .field("Position", format!("#{b} in queue"), false)
+        .field("Swapped With", format!("#{a} {other_title}"), false);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Shuffle the upcoming tracks in the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn shuffle(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+
+    let Some(next) = shuffle_upcoming(&queue) else {
+        ctx.say_ephemeral("Not enough tracks in the queue to shuffle")
+            .await?;
+        return Ok(());
+    };
+
+    let data = next.data::<TrackData>();
+    let embed = track_embed("Shuffled, Up Next", &data);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Shuffles every track after the current one, returning the track which is up next.
+fn shuffle_upcoming(queue: &TrackQueue) -> Option<TrackHandle> {
+    queue.modify_queue(|tracks| {
+        if tracks.len() < 3 {
+            return None;
+        }
+        tracks.make_contiguous()[1..].shuffle(&mut rand::thread_rng());
+        Some(tracks[1].handle())
+    })
+}
+
+/// Skip to a position in the queue, dropping every track before it
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn skipto(
+    ctx: Context<'_>,
+    #[description = "position to skip to"] position: usize,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+
+    let Some(queue) = guild_queue(ctx).await? else {
+        return Ok(());
+    };
+
+    let target = queue.modify_queue(|tracks| -> Result<_, String> {
+        check_upcoming(tracks.len(), position)?;
+        for track in tracks.drain(1..position - 1) {
+            drop(track.stop());
+        }
+        Ok(tracks[1].handle())
+    });
+    let target = match target {
+        Ok(target) => target,
+        Err(why) => {
+            ctx.say_ephemeral(why).await?;
+            return Ok(());
+        }
+    };
+    queue.skip()?;
+
+    let data = target.data::<TrackData>();
+    let embed = track_embed("Skipped To", &data);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Returns the queue of the guild, replying to the author if the bot is not in a voice channel.
+async fn guild_queue(ctx: Context<'_>) -> Result<Option<TrackQueue>> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(handler_lock) = ctx.data().songbird.get(guild_id) else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(None);
+    };
+
+    let handler = handler_lock.lock().await;
+    Ok(Some(handler.queue().clone()))
+}
+
+/// Checks that a 1-based queue position refers to an upcoming track and not the current one,
+/// explaining why it does not otherwise.
+fn check_upcoming(len: usize, position: usize) -> Result<(), String> {
+    if position == 1 {
+        return Err("Position 1 is the current track, use /skip instead".to_owned());
+    }
+    if position == 0 || position > len {
+        return Err(format!("Positions must be between 2 and {len}"));
+    }
+    Ok(())
+}
+
+/// Checks that an inclusive range of 1-based queue positions lies within the queue.
+fn check_range(len: usize, start: usize, end: usize) -> Result<(), String> {
+    if start == 0 || end > len {
+        return Err(format!("Positions must be between 1 and {len}"));
+    }
+    Ok(())
+}
+
+/// Clear all tracks in the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn clear(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    if let Some(handler_lock) = songbird.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        handler.queue().stop();
+        ctx.data()
+            .store
+            .blocking(move |store| store.delete_player(guild_id.get()))
+            .await?;
+        ctx.say("Cleared queue").await?;
+    } else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+    }
+    Ok(())
+}
+
+/// Skip the current playing track
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn skip(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    if songbird.get(guild_id).is_none() {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    }
+
+    let Some(member) = ctx.author_member().await else {
+        return Ok(());
+    };
+    let reply = skip_or_vote(ctx.serenity_context(), &ctx.data(), guild_id, &member).await?;
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Pause the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn pause(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    if let Some(handler_lock) = songbird.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        handler.queue().pause()?;
+        ctx.say("Paused queue").await?;
+    } else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+    };
+    Ok(())
+}
+
+/// Resume the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn resume(ctx: Context<'_>) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    if let Some(handler_lock) = songbird.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        handler.queue().resume()?;
+        ctx.say("Resumed queue").await?;
+    } else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+    }
+    Ok(())
+}
+
+/// Set the repeat mode of the queue
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn set_loop(
+    ctx: Context<'_>,
+    #[description = "new repeat mode"] mode: RepeatMode,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    let Some(handler_lock) = songbird.get(guild_id) else {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    };
+
+    ctx.data()
+        .sessions
+        .with(guild_id, |session| session.repeat = mode);
+    if let Some(current) = handler_lock.lock().await.queue().current() {
+        set_looping(&current, mode);
+    }
+
+    let reply = match mode {
+        RepeatMode::Off => "Disabled repeat",
+        RepeatMode::Track => "Repeating the current track",
+        RepeatMode::Queue => "Repeating the queue",
+        RepeatMode::ShuffleRepeat => "Repeating the queue in random order",
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Keep playing related tracks once the queue runs out
+#[poise::command(slash_command, category = "Music", guild_only)]
+pub async fn autoplay(
+    ctx: Context<'_>,
+    #[description = "turn autoplay on or off, toggles when not given"] enabled: Option<bool>,
+) -> Result<()> {
+    if !require_dj(ctx).await? {
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let songbird = ctx.data().songbird.clone();
+
+    if songbird.get(guild_id).is_none() {
+        ctx.say_ephemeral("Not in a voice channel").await?;
+        return Ok(());
+    }
+
+    let enabled = ctx.data().sessions.with(guild_id, |session| {
+        session.autoplay = enabled.unwrap_or(!session.autoplay);
+        session.autoplay
+    });
+
+    if enabled {
+        ctx.say("Enabled autoplay, related tracks will play once the queue runs out")
+            .await?;
+    } else {
+        ctx.say("Disabled autoplay").await?;
+    }
+    Ok(())
+}
+
+fn track_embed<'a>(header: &'a str, data: &'a TrackData) -> serenity::CreateEmbed<'a> {
+    let metadata = &data.metadata;
+    let (requester_name, requester) = if data.autoplayed {
+        ("Autoplayed", "Related to the last track".to_owned())
+    } else {
+        ("Requester", data.requester_label())
+    };
+
+    let title = metadata.title.as_deref().unwrap_or("No Title");
+    let channel = metadata.channel.as_deref().unwrap_or("No Channel");
+    let link = metadata.source_url.as_deref().unwrap_or("");
+
+    let footer = match (metadata.duration, data.clip) {
+        _ if data.live => "Live".to_owned(),
+        (Some(duration), Some(clip)) => format!(
+            "Duration: {}, playing {} of it",
+            duration_hhmmss(&duration),
+            duration_hhmmss(&clip.length(duration))
+        ),
+        (Some(duration), None) if !data.segments.is_empty() => format!(
+            "Duration: {} ({} with skipped segments)",
+            duration_hhmmss(&duration),
+            duration_hhmmss(&time_left(duration, Duration::ZERO, &data.segments))
+        ),
+        (Some(duration), None) => format!("Duration: {}", duration_hhmmss(&duration)),
+        (None, _) => "Duration: unknown".to_owned(),
+    };
+    let footer = serenity::CreateEmbedFooter::new(footer);
+
+    let mut embed = serenity::CreateEmbed::default()
+        .title(header)
+        .description(format!("### {title}"));
+    // library tracks have no link anyone else could open
+    if is_link(link) {
+        embed = embed.field("Link", format!("[click me]({link})"), true);
+    }
+    embed = embed
+        .field("Channel", channel, true)
+        .field(requester_name, requester, true);
+    if let Some(clip) = data.clip {
+        let end = clip
+            .end
+            .map_or("the end".to_owned(), |end| duration_hhmmss(&end));
+        embed = embed.field(
+            "Clip",
+            format!("{} to {end}", duration_hhmmss(&clip.start)),
+            true,
+        );
+    }
+    embed.footer(footer)
+}
+
+fn duration_hhmmss(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+    let seconds = secs % 60;
+    let minutes = (secs / 60) % 60;
+    let hours = (secs / 60) / 60;
+    format!("{hours:0>2}:{minutes:0>2}:{seconds:0>2}")
+}
+
+/// Shows the position in a track, along with a progress bar when its length is known.
+fn progress_field(position: &Duration, duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!(
+            "[{}/{}]\n{}",
+            duration_hhmmss(position),
+            duration_hhmmss(&duration),
+            progress_bar(position, &duration, 18)
+        ),
+        None => format!("[{}]", duration_hhmmss(position)),
+    }
+}
+
+/// Adds the chapter playing at `position`, for tracks which have chapters.
+fn chapter_field<'a>(
+    embed: serenity::CreateEmbed<'a>,
+    data: &TrackData,
+    position: &Duration,
+) -> serenity::CreateEmbed<'a> {
+    match current_chapter(&data.chapters, *position) {
+        Some((index, chapter)) => embed.field(
+            "Chapter",
+            format!("{}/{}: {}", index + 1, data.chapters.len(), chapter.title),
+            false,
+        ),
+        None => embed,
+    }
+}
+
+/// Adds the listening time and, when the station sends one, the current title of a live stream.
+fn live_fields<'a>(
+    embed: serenity::CreateEmbed<'a>,
+    position: &Duration,
+    title: Option<String>,
+) -> serenity::CreateEmbed<'a> {
+    let embed = embed.field(
+        "Progress",
+        format!("Live, listening for {}", duration_hhmmss(position)),
+        false,
+    );
+    match title {
+        Some(title) => embed.field("On Air", title, false),
+        None => embed,
+    }
+}
+
+/// Fetches the title a live radio stream is currently playing.
+async fn stream_title(data: &Data, track: &TrackData) -> Option<String> {
+    if !track.live {
+        return None;
+    }
+    icy_title(&data.public_http, track.metadata.source_url.as_deref()?).await
+}
+
+/// Shortens an autocomplete label, as discord rejects choice names longer than 100 characters.
+fn choice_label(label: String) -> String {
+    if label.chars().count() > 100 {
+        label.chars().take(97).collect::<String>() + "..."
+    } else {
+        label
+    }
+}
+
+/// Whether a song argument is a link rather than a search term.
+fn is_link(song: &str) -> bool {
+    song.starts_with("https://") || song.starts_with("http://")
+}
+
+/// Parses a position such as `3` or a range such as `3-7` into an inclusive range.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+        None => {
+            let position = range.trim().parse().ok()?;
+            (position, position)
+        }
+    };
+    (start <= end).then_some((start, end))
+}
+
+/// Checks that a clip lies within its track, explaining why it does not otherwise.
+///
+/// Every clip goes through this before it plays, whether it came with a link, from `/play` or was
+/// saved along with a track.
+fn check_clip(clip: &Clip, metadata: &AuxMetadata, live: bool) -> Result<(), String> {
+    if live {
+        return Err("Live streams cannot be clipped".to_owned());
+    }
+    let Some(duration) = metadata.duration else {
+        return Err("The length of this track is unknown, so it cannot be clipped".to_owned());
+    };
+    if !clip.fits(duration) {
+        return Err(format!(
+            "The clip has to start before it ends, within the {} of the track",
+            duration_hhmmss(&duration)
+        ));
+    }
+    Ok(())
+}
+
+/// Draws how far `current` is into a track ending at `end`.
+///
+/// Positions can run past a rounded duration, and live videos report a duration of zero, which
+/// shows as an empty bar.
+#[allow(clippy::cast_possible_truncation)]
+fn progress_bar(current: &Duration, end: &Duration, bar_length: usize) -> String {
+    let left = if end.is_zero() {
+        0
+    } else {
+        (bar_length as u128 * current.min(end).as_millis() / end.as_millis()) as usize
+    };
+    let right = bar_length - left;
+    format!("**[{}{}]**", "#".repeat(left), "-".repeat(right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decides_who_is_a_dj() {
+        let everyone = GuildSettings::default();
+        assert!(dj_rights(&everyone, false, false));
+
+        let votes = GuildSettings {
+            vote_skip: true,
+            ..GuildSettings::default()
+        };
+        assert!(!dj_rights(&votes, false, false));
+        assert!(dj_rights(&votes, true, false));
+
+        let role = GuildSettings {
+            dj_role: Some(1),
+            ..GuildSettings::default()
+        };
+        assert!(!dj_rights(&role, false, false));
+        assert!(dj_rights(&role, false, true));
+        assert!(dj_rights(&role, true, false));
+    }
+
+    #[test]
+    fn counts_votes_needed() {
+        assert_eq!(votes_needed(4, 50), 2);
+        assert_eq!(votes_needed(5, 50), 3);
+        assert_eq!(votes_needed(3, 100), 3);
+        assert_eq!(votes_needed(10, 1), 1);
+        // a listener alone always gets to skip
+        assert_eq!(votes_needed(1, 50), 1);
+        assert_eq!(votes_needed(0, 50), 1);
+    }
+
+    #[test]
+    fn parses_positions_and_ranges() {
+        assert_eq!(parse_range("3"), Some((3, 3)));
+        assert_eq!(parse_range(" 3 "), Some((3, 3)));
+        assert_eq!(parse_range("3-7"), Some((3, 7)));
+        assert_eq!(parse_range("3 - 7"), Some((3, 7)));
+        assert_eq!(parse_range("4-4"), Some((4, 4)));
+        assert_eq!(parse_range("7-3"), None);
+        assert_eq!(parse_range("-3"), None);
+        assert_eq!(parse_range("3-"), None);
+        assert_eq!(parse_range("three"), None);
+        assert_eq!(parse_range(""), None);
+    }
+
+    #[test]
+    fn checks_queue_positions() {
+        assert!(check_upcoming(5, 2).is_ok());
+        assert!(check_upcoming(5, 5).is_ok());
+        assert!(check_upcoming(5, 1).is_err());
+        assert!(check_upcoming(5, 0).is_err());
+        assert!(check_upcoming(5, 6).is_err());
+        // the queue may have shrunk since the command was sent
+        assert!(check_upcoming(1, 2).is_err());
+
+        assert!(check_range(5, 1, 5).is_ok());
+        assert!(check_range(5, 0, 2).is_err());
+        assert!(check_range(5, 3, 6).is_err());
+        assert!(check_range(0, 1, 1).is_err());
+    }
+
+    #[test]
+    fn progress_bar_fills_with_position() {
+        let end = Duration::from_secs(100);
+        assert_eq!(progress_bar(&Duration::ZERO, &end, 4), "**[----]**");
+        assert_eq!(
+            progress_bar(&Duration::from_secs(50), &end, 4),
+            "**[##--]**"
+        );
+        assert_eq!(progress_bar(&end, &end, 4), "**[####]**");
+    }
+
+    #[test]
+    fn progress_bar_handles_odd_durations() {
+        let short = Duration::from_millis(500);
+        assert_eq!(
+            progress_bar(&Duration::from_millis(250), &short, 4),
+            "**[##--]**"
+        );
+        assert_eq!(
+            progress_bar(&Duration::from_secs(5), &short, 4),
+            "**[####]**"
+        );
+        assert_eq!(
+            progress_bar(&Duration::from_secs(5), &Duration::ZERO, 4),
+            "**[----]**"
+        );
+    }
+}
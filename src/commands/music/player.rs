@@ -0,0 +1,246 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use poise::serenity_prelude as serenity;
+use songbird::{
+    tracks::{PlayMode, TrackQueue},
+    Event, EventContext, EventHandler,
+};
+use tracing::warn;
+
+use super::{
+    chapter_field, is_dj, live_fields, progress_field, set_looping, shuffle_upcoming, skip_or_vote,
+    stream_title, track_embed, TrackData,
+};
+use crate::Data;
+
+/// How often the player message is edited to move the progress bar.
+pub(super) const PLAYER_REFRESH: Duration = Duration::from_secs(15);
+/// The player is edited in place while it is among this many of the latest messages in its
+/// channel, otherwise it is posted again so it does not get buried.
+const RECENT_MESSAGES: u8 = 5;
+
+/// Keeps the single player message of a guild up to date.
+pub(super) struct PlayerUpdater {
+    pub guild_id: serenity::GuildId,
+    pub queue: TrackQueue,
+    pub http: Arc<serenity::Http>,
+    pub data: Arc<Data>,
+    /// Whether this fires on track changes, which may move the player to the bottom of the
+    /// channel, or only periodically to move the progress bar.
+    pub track_change: bool,
+}
+
+#[async_trait]
+impl EventHandler for PlayerUpdater {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        let result = update_player(
+            &self.http,
+            &self.data,
+            self.guild_id,
+            &self.queue,
+            self.track_change,
+        )
+        .await;
+
+        if let Err(why) = result {
+            warn!(
+                "could not update player of guild {}: {why:?}",
+                self.guild_id
+            );
+        }
+        None
+    }
+}
+
+/// Edits the player message of a guild to show the current track, posting it if there is none.
+///
+/// With `repost` the player is posted again, deleting the old one, when it has been buried
+/// under newer messages.
+pub(super) async fn update_player(
+    http: &serenity::Http,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    queue: &TrackQueue,
+    repost: bool,
+) -> Result<()> {
+    let (message, text_channel, repeat) = data.sessions.with(guild_id, |session| {
+        (session.player_message, session.text_channel, session.repeat)
+    });
+
+    let Some(track) = queue.current() else {
+        // leave the last player behind without controls once the queue is done
+        if let Some((channel, message)) = message {
+            let embed = serenity::CreateEmbed::new().title("Queue Finished");
+            let edit = serenity::EditMessage::new().embed(embed).components(vec![]);
+            channel.edit_message(http, message, edit).await?;
+            data.sessions
+                .with(guild_id, |session| session.player_message = None);
+        }
+        return Ok(());
+    };
+
+    let state = track.get_info().await?;
+    let paused = matches!(state.playing, PlayMode::Pause);
+    let track_data = track.data::<TrackData>();
+    let header = if paused { "Paused" } else { "Now Playing" };
+    let embed = if track_data.live {
+        let title = stream_title(data, &track_data).await;
+        live_fields(track_embed(header, &track_data), &state.position, title)
+    } else {
+        let position = track_data.position();
+        let embed = track_embed(header, &track_data).field(
+            "Progress",
+            progress_field(&position, track_data.metadata.duration),
+            false,
+        );
+        chapter_field(embed, &track_data, &position)
+    }
+    .field("Repeat", repeat.label(), true);
+    let buttons = player_buttons(paused);
+
+    if let Some((channel, id)) = message {
+        if !repost || is_recent(http, channel, id).await? {
+            let edit = serenity::EditMessage::new()
+                .embed(embed)
+                .components(vec![buttons]);
+            channel.edit_message(http, id, edit).await?;
+            return Ok(());
+        }
+        channel.delete_message(http, id, None).await.ok();
+    }
+
+    let Some(channel) = message.map(|(channel, _)| channel).or(text_channel) else {
+        return Ok(());
+    };
+    let post = serenity::CreateMessage::new()
+        .embed(embed)
+        .components(vec![buttons]);
+    let posted = channel.send_message(http, post).await?;
+    data.sessions.with(guild_id, |session| {
+        session.player_message = Some((channel, posted.id));
+    });
+    Ok(())
+}
+
+/// Whether a message is among the latest messages of its channel.
+async fn is_recent(
+    http: &serenity::Http,
+    channel: serenity::ChannelId,
+    message: serenity::MessageId,
+) -> Result<bool> {
+    let latest = channel
+        .messages(http, serenity::GetMessages::new().limit(RECENT_MESSAGES))
+        .await?;
+    Ok(latest.iter().any(|latest| latest.id == message))
+}
+
+fn player_buttons<'a>(paused: bool) -> serenity::CreateActionRow<'a> {
+    serenity::CreateActionRow::Buttons(
+        vec![
+            serenity::CreateButton::new("player:toggle")
+                .style(serenity::ButtonStyle::Primary)
+                .label(if paused { "Resume" } else { "Pause" }),
+            serenity::CreateButton::new("player:skip")
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Skip"),
+            serenity::CreateButton::new("player:stop")
+                .style(serenity::ButtonStyle::Danger)
+                .label("Stop"),
+            serenity::CreateButton::new("player:loop")
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Loop"),
+            serenity::CreateButton::new("player:shuffle")
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Shuffle"),
+        ]
+        .into(),
+    )
+}
+
+/// Handles presses of the player buttons, which go through the same checks as the commands.
+pub async fn handle_player_button(
+    ctx: &serenity::Context,
+    data: Arc<Data>,
+    press: &serenity::ComponentInteraction,
+) -> Result<()> {
+    let Some(action) = press.data.custom_id.strip_prefix("player:") else {
+        return Ok(());
+    };
+    let (Some(guild_id), Some(member)) = (press.guild_id, press.member.as_ref()) else {
+        return Ok(());
+    };
+    let Some(handler_lock) = data.songbird.get(guild_id) else {
+        return respond(ctx, press, "Not in a voice channel".to_owned()).await;
+    };
+    let queue = handler_lock.lock().await.queue().clone();
+    let settings = data
+        .store
+        .blocking(move |store| store.guild_settings(guild_id.get()))
+        .await?;
+
+    let reply = match action {
+        "skip" => skip_or_vote(ctx, &data, guild_id, member).await?,
+        "toggle" | "stop" | "loop" | "shuffle" if !is_dj(member, &settings) => {
+            "Only DJs can do that".to_owned()
+        }
+        "toggle" => {
+            let Some(track) = queue.current() else {
+                return respond(ctx, press, "Nothing is playing right now".to_owned()).await;
+            };
+            if matches!(track.get_info().await?.playing, PlayMode::Pause) {
+                queue.resume()?;
+                "Resumed queue".to_owned()
+            } else {
+                queue.pause()?;
+                "Paused queue".to_owned()
+            }
+        }
+        "stop" => {
+            queue.stop();
+            data.store
+                .blocking(move |store| store.delete_player(guild_id.get()))
+                .await?;
+            "Cleared queue".to_owned()
+        }
+        "loop" => {
+            let mode = data.sessions.with(guild_id, |session| {
+                session.repeat = session.repeat.next();
+                session.repeat
+            });
+            if let Some(current) = queue.current() {
+                set_looping(&current, mode);
+            }
+            format!("Repeat mode is now {}", mode.label())
+        }
+        "shuffle" => match shuffle_upcoming(&queue) {
+            Some(_) => "Shuffled queue".to_owned(),
+            None => "Not enough tracks in the queue to shuffle".to_owned(),
+        },
+        _ => return Ok(()),
+    };
+
+    respond(ctx, press, reply).await?;
+
+    // reflect the new state on the buttons
+    update_player(&ctx.http, &data, guild_id, &queue, false).await
+}
+
+async fn respond(
+    ctx: &serenity::Context,
+    press: &serenity::ComponentInteraction,
+    content: String,
+) -> Result<()> {
+    let response = serenity::CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    press
+        .create_response(
+            &ctx.http,
+            serenity::CreateInteractionResponse::Message(response),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use poise::serenity_prelude as serenity;
+use songbird::{
+    tracks::{PlayMode, TrackQueue},
+    Event, EventContext, EventHandler,
+};
+use tracing::warn;
+
+use super::TrackData;
+use crate::audio::segments::segment_end;
+
+/// How often the position of the current track is checked for skipped segments.
+pub(super) const SEGMENT_CHECK: Duration = Duration::from_millis(250);
+
+/// Seeks past the skipped segments of the current track once playback enters one.
+///
+/// Outros are left to [`TrackData::end`] instead, so the track ends by itself and repeats or
+/// moves on as usual.
+pub(super) struct SegmentSkipper {
+    pub guild_id: serenity::GuildId,
+    pub queue: TrackQueue,
+}
+
+impl SegmentSkipper {
+    async fn check(&self) -> Result<()> {
+        let Some(current) = self.queue.current() else {
+            return Ok(());
+        };
+        let track_data = current.data::<TrackData>();
+        if track_data.segments.is_empty() {
+            return Ok(());
+        }
+
+        let state = current.get_info().await?;
+        if state.playing != PlayMode::Play {
+            return Ok(());
+        }
+
+        let position = track_data.position();
+        let Some(end) = segment_end(&track_data.segments, position) else {
+            return Ok(());
+        };
+
+        // an outro running until the end is never reached, the stream ends where it starts
+        drop(current.seek(end));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for SegmentSkipper {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if let Err(why) = self.check().await {
+            warn!("could not skip segment in guild {}: {why:?}", self.guild_id);
+        }
+        None
+    }
+}
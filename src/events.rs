@@ -1,19 +1,41 @@
+use std::sync::atomic::Ordering;
+
 use anyhow::Result;
 use tracing::info;
 
 use poise::serenity_prelude as serenity;
 use serenity::FullEvent as Event;
 
-use crate::FrameworkContext;
+use crate::{commands::music, Data, FrameworkContext};
 
 pub async fn event_handler(ctx: FrameworkContext<'_>, event: &Event) -> Result<()> {
     match event {
         Event::Ready { data_about_bot } => ready(ctx, data_about_bot).await,
+        Event::VoiceStateUpdate { new, .. } => voice_state_update(ctx, new).await,
+        Event::InteractionCreate {
+            interaction: serenity::Interaction::Component(press),
+        } => {
+            let data = ctx.serenity_context.data::<Data>();
+            music::handle_player_button(ctx.serenity_context, data, press).await
+        }
         _ => Ok(()),
     }
 }
 
-async fn ready(_ctx: FrameworkContext<'_>, data: &serenity::Ready) -> Result<()> {
+async fn ready(ctx: FrameworkContext<'_>, data: &serenity::Ready) -> Result<()> {
     info!("Logged in as {}", data.user.name);
+
+    let user_data = ctx.user_data();
+    if !user_data.players_restored.swap(true, Ordering::Relaxed) {
+        music::restore_players(ctx.serenity_context, &user_data).await?;
+    }
     Ok(())
 }
+
+async fn voice_state_update(ctx: FrameworkContext<'_>, state: &serenity::VoiceState) -> Result<()> {
+    let Some(guild_id) = state.guild_id else {
+        return Ok(());
+    };
+    let data = ctx.serenity_context.data::<Data>();
+    music::check_alone(ctx.serenity_context, data, guild_id).await
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{atomic::AtomicBool, Arc};
 
 use anyhow::{Error, Result};
 use poise::serenity_prelude as serenity;
@@ -6,13 +6,27 @@ use tracing::{error, warn};
 
 use yinfo::{ClientConfig, ClientType, Innertube};
 
-use crate::traits::ContextExt;
+use crate::{
+    audio::{
+        cache::AudioCache,
+        history::History,
+        library::{Library, LibraryResolver},
+        resolver::{Resolvers, SourceResolver},
+        search::SearchCache,
+        segments::{SponsorBlock, DEFAULT_API},
+        session::Sessions,
+        sources::{public_client, HttpResolver, YouTubeResolver},
+    },
+    store::Store,
+    traits::ContextExt,
+};
 
 mod audio;
 mod commands;
 
 mod events;
 mod paginate;
+mod store;
 mod traits;
 
 type Context<'a> = poise::Context<'a, Data, Error>;
@@ -22,8 +36,23 @@ type FrameworkContext<'a> = poise::FrameworkContext<'a, Data, Error>;
 pub struct Data {
     start_time: std::time::SystemTime,
     reqwest: reqwest::Client,
+    /// Client for links users send directly, which stays off private addresses.
+    public_http: reqwest::Client,
     songbird: Arc<songbird::Songbird>,
     innertube: Arc<Innertube>,
+    resolvers: Resolvers,
+    /// Music files hosted by the server, if a library directory is set.
+    library: Option<Arc<Library>>,
+    /// Streams kept on disk, if a cache directory is set.
+    cache: Option<Arc<AudioCache>>,
+    search_cache: SearchCache,
+    sessions: Sessions,
+    history: History,
+    store: Arc<Store>,
+    /// Maximum number of entries a single playlist import can add.
+    playlist_limit: usize,
+    /// Saved players are only restored on the first ready event, not on reconnects.
+    players_restored: AtomicBool,
 }
 
 #[tokio::main]
@@ -47,13 +76,69 @@ async fn main() {
     };
     let innertube = Arc::new(Innertube::new(config).unwrap());
 
+    let library = std::env::var("LIBRARY")
+        .ok()
+        .map(|dir| Arc::new(Library::new(dir)));
+
+    // cache size is given in megabytes
+    let cache_size = std::env::var("CACHE_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<u64>().ok())
+        .unwrap_or(2048);
+    let cache = std::env::var("CACHE").ok().map(|dir| {
+        Arc::new(AudioCache::open(dir, cache_size * 1024 * 1024).expect("Could not open cache"))
+    });
+
+    // a local mirror of the segment database can stand in for the public api
+    let sponsorblock_api =
+        std::env::var("SPONSORBLOCK_API").unwrap_or_else(|_| DEFAULT_API.to_owned());
+    let sponsorblock = SponsorBlock::new(reqwest.clone(), sponsorblock_api);
+
+    let mut resolvers: Vec<Arc<dyn SourceResolver>> = vec![Arc::new(YouTubeResolver::new(
+        innertube.clone(),
+        reqwest.clone(),
+        cache.clone(),
+        sponsorblock,
+    ))];
+    if let Some(library) = &library {
+        library.clone().watch();
+        resolvers.push(Arc::new(LibraryResolver::new(library.clone())));
+    }
+    // the http resolver accepts any link, so it goes last
+    let public_http = public_client().expect("Could not build http client");
+    resolvers.push(Arc::new(HttpResolver::new(public_http.clone())));
+    let resolvers = Resolvers::new(resolvers);
+
+    let database = std::env::var("DATABASE").unwrap_or_else(|_| "kirbean.db".to_owned());
+    let store = Arc::new(Store::open(database).expect("Could not open database"));
+
+    let playlist_limit = std::env::var("PLAYLIST_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(100);
+
+    // history is only kept in memory unless asked for
+    let history = if std::env::var("PERSIST_HISTORY").is_ok() {
+        History::new(Some(store.clone()))
+    } else {
+        History::new(None)
+    };
+
     let data = Arc::new(Data {
         start_time,
         reqwest,
-        songbird: songbird::Songbird::serenity_from_config(
-            songbird::Config::default().use_softclip(false),
-        ),
+        public_http,
+        songbird: songbird::Songbird::serenity(),
         innertube,
+        resolvers,
+        library,
+        cache,
+        search_cache: SearchCache::default(),
+        sessions: Sessions::default(),
+        history,
+        store,
+        playlist_limit,
+        players_restored: AtomicBool::new(false),
     });
 
     let intents =
@@ -0,0 +1,553 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{
+    clip::Clip,
+    history::HistoryEntry,
+    session::{FadeCurve, RepeatMode},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS players (
+    guild_id INTEGER PRIMARY KEY,
+    voice_channel INTEGER NOT NULL,
+    text_channel INTEGER NOT NULL,
+    position_ms INTEGER NOT NULL DEFAULT 0,
+    repeat_mode TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS queue_tracks (
+    guild_id INTEGER NOT NULL,
+    idx INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    requester INTEGER,
+    autoplayed INTEGER NOT NULL DEFAULT 0,
+    clip TEXT,
+    PRIMARY KEY (guild_id, idx)
+);
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id INTEGER PRIMARY KEY,
+    settings TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    played_at INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    requester TEXT NOT NULL,
+    clip TEXT
+);
+CREATE INDEX IF NOT EXISTS history_guild ON history (guild_id, id);
+";
+
+/// Changes to the tables of databases created by earlier versions, in order.
+///
+/// `user_version` counts how many of them a database has had. [`SCHEMA`] already creates tables
+/// the way they end up, so fresh databases start out with all of them.
+const MIGRATIONS: [fn(&Connection) -> rusqlite::Result<()>; 4] =
+    [repeat_modes, requester_ids, autoplayed_tracks, clips];
+
+/// Settings a guild's admins can change. Stored as json so new settings need no migration.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Volume in percent every session starts with.
+    pub volume: u16,
+    /// Seconds to stay in a voice channel without any listeners.
+    pub alone_timeout: u64,
+    /// Seconds to stay in a voice channel with an empty queue.
+    pub idle_timeout: u64,
+    /// Never leave the voice channel on its own.
+    pub always_on: bool,
+    /// Role allowed to control playback. When unset everyone can, unless skips go by vote.
+    pub dj_role: Option<u64>,
+    /// Whether skips by members who are not DJs count as votes.
+    pub vote_skip: bool,
+    /// Percent of listeners needed to skip a track by vote.
+    pub vote_threshold: u8,
+    /// Whether tracks are evened out to the same loudness.
+    pub normalize: bool,
+    /// Seconds tracks fade into each other, 0 for hard cuts.
+    pub crossfade: u64,
+    pub fade_curve: FadeCurve,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        GuildSettings {
+            volume: 100,
+            alone_timeout: 60,
+            idle_timeout: 300,
+            always_on: false,
+            dj_role: None,
+            vote_skip: false,
+            vote_threshold: 50,
+            normalize: false,
+            crossfade: 0,
+            fade_curve: FadeCurve::default(),
+        }
+    }
+}
+
+/// A guild's player as it was last saved, used to rebuild the queue after a restart.
+pub struct SavedPlayer {
+    pub guild_id: u64,
+    pub voice_channel: u64,
+    pub text_channel: u64,
+    /// Playback position of the first track in `tracks`.
+    pub position: Duration,
+    pub repeat: RepeatMode,
+    pub tracks: Vec<SavedTrack>,
+}
+
+pub struct SavedTrack {
+    pub url: String,
+    /// User id of whoever requested the track.
+    pub requester: Option<u64>,
+    pub autoplayed: bool,
+    pub clip: Option<Clip>,
+}
+
+/// Small SQLite backed store for state which should outlive the bot process.
+///
+/// Writes are tiny and infrequent, so a single connection behind a mutex is enough.
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut conn = Connection::open(path)?;
+        let fresh = !has_table(&conn, "players")?;
+        conn.execute_batch(SCHEMA)?;
+        if fresh {
+            conn.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        } else {
+            migrate(&mut conn)?;
+        }
+        Ok(Store {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Runs `f` on a blocking thread, so queries do not hold up the async runtime.
+    pub async fn blocking<T: Send + 'static>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&Store) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store)).await?
+    }
+
+    /// Replaces the saved player of a guild. An empty queue removes it instead.
+    pub fn save_player(&self, player: &SavedPlayer) -> Result<()> {
+        if player.tracks.is_empty() {
+            return self.delete_player(player.guild_id);
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO players
+                (guild_id, voice_channel, text_channel, position_ms, repeat_mode)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                player.guild_id,
+                player.voice_channel,
+                player.text_channel,
+                u64::try_from(player.position.as_millis()).unwrap_or(0),
+                serde_json::to_string(&player.repeat)?,
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM queue_tracks WHERE guild_id = ?1",
+            params![player.guild_id],
+        )?;
+        for (idx, track) in player.tracks.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO queue_tracks (guild_id, idx, url, requester, autoplayed, clip)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    player.guild_id,
+                    idx,
+                    track.url,
+                    track.requester,
+                    track.autoplayed,
+                    clip_to_json(track.clip)?
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_player(&self, guild_id: u64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM players WHERE guild_id = ?1", params![guild_id])?;
+        tx.execute(
+            "DELETE FROM queue_tracks WHERE guild_id = ?1",
+            params![guild_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn guild_settings(&self, guild_id: u64) -> Result<GuildSettings> {
+        let conn = self.conn.lock().unwrap();
+        let settings: Option<String> = conn
+            .query_row(
+                "SELECT settings FROM guild_settings WHERE guild_id = ?1",
+                params![guild_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match settings {
+            Some(settings) => Ok(serde_json::from_str(&settings)?),
+            None => Ok(GuildSettings::default()),
+        }
+    }
+
+    pub fn save_guild_settings(&self, guild_id: u64, settings: &GuildSettings) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO guild_settings (guild_id, settings) VALUES (?1, ?2)",
+            params![guild_id, serde_json::to_string(settings)?],
+        )?;
+        Ok(())
+    }
+
+    /// Adds a played track to the history of a guild, keeping only the latest `limit` entries.
+    pub fn record_history(&self, guild_id: u64, entry: &HistoryEntry, limit: usize) -> Result<()> {
+        let played_at = entry
+            .played_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO history (guild_id, played_at, url, title, requester, clip)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                guild_id,
+                played_at,
+                entry.url,
+                entry.title,
+                entry.requester,
+                clip_to_json(entry.clip)?
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM history WHERE guild_id = ?1 AND id NOT IN
+                (SELECT id FROM history WHERE guild_id = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![guild_id, limit],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the history of a guild, newest first.
+    pub fn history(&self, guild_id: u64) -> Result<Vec<HistoryEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let entries = conn
+            .prepare(
+                "SELECT played_at, url, title, requester, clip FROM history
+                    WHERE guild_id = ?1 ORDER BY id DESC",
+            )?
+            .query_map(params![guild_id], history_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Removes and returns the newest history entry of a guild.
+    pub fn pop_history(&self, guild_id: u64) -> Result<Option<HistoryEntry>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let entry = tx
+            .query_row(
+                "SELECT id, played_at, url, title, requester, clip FROM history
+                    WHERE guild_id = ?1 ORDER BY id DESC LIMIT 1",
+                params![guild_id],
+                |row| Ok((row.get::<_, i64>(0)?, history_entry_at(row, 1)?)),
+            )
+            .optional()?;
+
+        let Some((id, entry)) = entry else {
+            return Ok(None);
+        };
+        tx.execute("DELETE FROM history WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(Some(entry))
+    }
+
+    /// Loads every saved player along with its queue in order.
+    pub fn load_players(&self) -> Result<Vec<SavedPlayer>> {
+        let conn = self.conn.lock().unwrap();
+        let mut players = conn
+            .prepare(
+                "SELECT guild_id, voice_channel, text_channel, position_ms, repeat_mode FROM players",
+            )?
+            .query_map([], |row| {
+                Ok(SavedPlayer {
+                    guild_id: row.get(0)?,
+                    voice_channel: row.get(1)?,
+                    text_channel: row.get(2)?,
+                    position: Duration::from_millis(row.get(3)?),
+                    repeat: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+                    tracks: Vec::new(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT url, requester, autoplayed, clip FROM queue_tracks
+                WHERE guild_id = ?1 ORDER BY idx",
+        )?;
+        for player in &mut players {
+            player.tracks = stmt
+                .query_map(params![player.guild_id], |row| {
+                    Ok(SavedTrack {
+                        url: row.get(0)?,
+                        requester: row.get(1)?,
+                        autoplayed: row.get(2)?,
+                        clip: clip_from_json(row.get(3)?),
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+        Ok(players)
+    }
+}
+
+fn history_entry(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    history_entry_at(row, 0)
+}
+
+/// Reads a history entry from the columns starting at `start`.
+fn history_entry_at(row: &rusqlite::Row, start: usize) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        played_at: UNIX_EPOCH + Duration::from_secs(row.get(start)?),
+        url: row.get(start + 1)?,
+        title: row.get(start + 2)?,
+        requester: row.get(start + 3)?,
+        clip: clip_from_json(row.get(start + 4)?),
+    })
+}
+
+fn clip_to_json(clip: Option<Clip>) -> serde_json::Result<Option<String>> {
+    clip.map(|clip| serde_json::to_string(&clip)).transpose()
+}
+
+/// Reads a clip saved as JSON, leaving out one which cannot be read rather than the whole row.
+fn clip_from_json(json: Option<String>) -> Option<Clip> {
+    serde_json::from_str(&json?).ok()
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let tx = conn.transaction()?;
+    for migration in MIGRATIONS.iter().skip(version) {
+        migration(&tx)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Replaces the looping flag of saved players with their repeat mode.
+///
+/// Databases from before `user_version` was kept are at 0 whether they had the flag or not.
+fn repeat_modes(conn: &Connection) -> rusqlite::Result<()> {
+    if !has_column(conn, "players", "looping")? {
+        return Ok(());
+    }
+    // the flag only ever repeated the current track
+    conn.execute_batch(
+        r#"
+        ALTER TABLE players ADD COLUMN repeat_mode TEXT NOT NULL DEFAULT '"Off"';
+        UPDATE players SET repeat_mode = '"Track"' WHERE looping != 0;
+        ALTER TABLE players DROP COLUMN looping;
+        "#,
+    )
+}
+
+/// Saves who requested queued tracks by user id rather than by name, which anyone can share.
+///
+/// Names cannot be turned back into ids, so tracks queued before lose their requester.
+fn requester_ids(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE queue_tracks_by_id (
+            guild_id INTEGER NOT NULL,
+            idx INTEGER NOT NULL,
+            url TEXT NOT NULL,
+            requester INTEGER,
+            PRIMARY KEY (guild_id, idx)
+        );
+        INSERT INTO queue_tracks_by_id (guild_id, idx, url)
+            SELECT guild_id, idx, url FROM queue_tracks;
+        DROP TABLE queue_tracks;
+        ALTER TABLE queue_tracks_by_id RENAME TO queue_tracks;
+        ",
+    )
+}
+
+/// Saves whether queued tracks were picked by autoplay, so they do not restore as requested.
+fn autoplayed_tracks(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("ALTER TABLE queue_tracks ADD COLUMN autoplayed INTEGER NOT NULL DEFAULT 0;")
+}
+
+/// Saves the clips of queued tracks and played ones, so they play the same part again.
+fn clips(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("ALTER TABLE queue_tracks ADD COLUMN clip TEXT;")?;
+    // databases from before the history was kept just had it created with the column
+    if !has_column(conn, "history", "clip")? {
+        conn.execute_batch("ALTER TABLE history ADD COLUMN clip TEXT;")?;
+    }
+    Ok(())
+}
+
+fn has_table(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")?
+        .exists(params![table])
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    conn.prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?
+        .exists(params![table, column])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_first_schema() {
+        let path = std::env::temp_dir().join(format!("store-first-{}.db", std::process::id()));
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE players (
+                guild_id INTEGER PRIMARY KEY,
+                voice_channel INTEGER NOT NULL,
+                text_channel INTEGER NOT NULL,
+                position_ms INTEGER NOT NULL DEFAULT 0,
+                looping INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE queue_tracks (
+                guild_id INTEGER NOT NULL,
+                idx INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                requester TEXT NOT NULL,
+                PRIMARY KEY (guild_id, idx)
+            );
+            INSERT INTO players VALUES (1, 10, 100, 0, 1), (2, 20, 200, 0, 0);
+            INSERT INTO queue_tracks VALUES (1, 0, 'https://example.com/a.mp3', 'someone');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = Store::open(&path).unwrap();
+        let players = store.load_players().unwrap();
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+
+        let player = |guild_id| {
+            players
+                .iter()
+                .find(|player| player.guild_id == guild_id)
+                .unwrap()
+        };
+        assert_eq!(player(1).repeat, RepeatMode::Track);
+        assert_eq!(player(2).repeat, RepeatMode::Off);
+
+        let tracks = &player(1).tracks;
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].url, "https://example.com/a.mp3");
+        assert_eq!(tracks[0].requester, None);
+        assert!(!tracks[0].autoplayed);
+        assert_eq!(tracks[0].clip, None);
+    }
+
+    #[test]
+    fn saves_players() {
+        let store = Store::open(":memory:").unwrap();
+        let track = |url: &str, requester, autoplayed| SavedTrack {
+            url: url.to_owned(),
+            requester,
+            autoplayed,
+            clip: None,
+        };
+        let clip = Clip {
+            start: Duration::from_secs(30),
+            end: Some(Duration::from_secs(90)),
+        };
+        store
+            .save_player(&SavedPlayer {
+                guild_id: 1,
+                voice_channel: 10,
+                text_channel: 100,
+                position: Duration::from_secs(42),
+                repeat: RepeatMode::Queue,
+                tracks: vec![
+                    SavedTrack {
+                        clip: Some(clip),
+                        ..track("https://example.com/a.mp3", Some(7), false)
+                    },
+                    track("https://example.com/b.mp3", None, true),
+                ],
+            })
+            .unwrap();
+
+        let players = store.load_players().unwrap();
+        assert_eq!(players.len(), 1);
+        let player = &players[0];
+        assert_eq!(player.position, Duration::from_secs(42));
+        assert_eq!(player.repeat, RepeatMode::Queue);
+        let tracks: Vec<_> = player
+            .tracks
+            .iter()
+            .map(|track| {
+                let url = track.url.as_str();
+                (url, track.requester, track.autoplayed, track.clip)
+            })
+            .collect();
+        assert_eq!(
+            tracks,
+            [
+                ("https://example.com/a.mp3", Some(7), false, Some(clip)),
+                ("https://example.com/b.mp3", None, true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_clips_in_history() {
+        let store = Store::open(":memory:").unwrap();
+        let clip = Clip {
+            start: Duration::from_secs(30),
+            end: None,
+        };
+        let entry = HistoryEntry {
+            url: "https://example.com/a.mp3".to_owned(),
+            title: "A".to_owned(),
+            requester: "Autoplay".to_owned(),
+            clip: Some(clip),
+            played_at: UNIX_EPOCH,
+        };
+        store.record_history(1, &entry, 10).unwrap();
+
+        assert_eq!(store.history(1).unwrap()[0].clip, Some(clip));
+        assert_eq!(store.pop_history(1).unwrap().unwrap().clip, Some(clip));
+        assert!(store.pop_history(1).unwrap().is_none());
+    }
+}